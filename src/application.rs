@@ -1,18 +1,110 @@
-use std::path::PathBuf;
+use std::{borrow::Cow, collections::BTreeMap};
+#[cfg(feature = "gui")]
+use std::{
+    fmt, mem,
+    path::{Component, Path, PathBuf},
+    sync::{
+        Arc, LazyLock,
+        atomic::{AtomicBool, Ordering},
+    },
+    thread,
+    time::Duration,
+};
 
+#[cfg(feature = "gui")]
 use iced::{
-    Length, Task, Theme,
-    widget::{Column, Text, button, horizontal_rule, scrollable, text},
+    Length, Subscription, Task, Theme,
+    futures::{Stream, StreamExt},
+    task,
+    widget::{Column, Row, button, canvas, checkbox, pick_list, slider, text, text_input},
 };
 
+#[cfg(feature = "gui")]
 use rfd::AsyncFileDialog;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+#[cfg(feature = "gui")]
+mod chart;
+#[cfg(feature = "gui")]
+use chart::{Sparkline, TrendChart};
+
+#[cfg(feature = "gui")]
+mod number_format;
+#[cfg(feature = "gui")]
+use number_format::NumberFormat;
+
+mod diagnostics;
+#[cfg(feature = "gui")]
+use diagnostics::{find_duplicate_profiles, parse_warning_report, unknown_tag_report};
+
+mod edits;
+#[cfg(feature = "gui")]
+use edits::Edits;
+use edits::Field;
+
+pub(crate) mod export;
+#[cfg(feature = "gui")]
+use export::ExportPreset;
+
+pub(crate) mod metric;
+#[cfg(feature = "gui")]
+use metric::Metric;
+
+pub(crate) mod units;
+use units::Units;
+
+mod merge;
+#[cfg(feature = "gui")]
+use merge::MergeTolerance;
+
+pub(crate) mod norms;
+
+#[cfg(feature = "serde")]
+pub(crate) mod schema;
+
+#[cfg(feature = "gui")]
+mod table;
+#[cfg(feature = "gui")]
+use table::TableBuilder;
+
+mod tags;
+
+pub(crate) mod derived;
+#[cfg(feature = "gui")]
+use derived::{DeriveFn, DerivedColumn};
+
+#[cfg(feature = "gui")]
+mod session;
+#[cfg(feature = "gui")]
+use session::SessionFormat;
+
+#[cfg(feature = "gui")]
+mod settings;
+#[cfg(feature = "gui")]
+use settings::{FONT_SCALE_RANGE, MERGE_SECONDS_RANGE, MERGE_WEIGHT_KG_RANGE, Settings};
 
-mod general_data_structs;
-use general_data_structs::{Date, DateTime, Gender};
+mod stats;
+#[cfg(feature = "gui")]
+use stats::Period;
 
-mod parser;
-use parser::{DataRaw, ProfRaw, RawUserRecord, TanitaParser};
+mod field_tag;
+use field_tag::FieldTag;
 
+mod model_profile;
+
+pub(crate) mod general_data_structs;
+use general_data_structs::{ActivityLevel, BodyType, Date, DateTime, DeviceModel, Gender, Percent};
+
+pub(crate) mod parser;
+use parser::{DataRaw, ParseWarning, ProfRaw, RawUserRecord};
+#[cfg(feature = "gui")]
+use parser::{
+    DatasetWarning, ParseProgress, ParsedDataset, TanitaParser, TanitaValidationError, WarningKind,
+    find_card_folder, find_tanita_folders,
+};
+
+#[cfg(feature = "gui")]
 async fn pick_folder() -> Option<PathBuf> {
     let file_handle = AsyncFileDialog::new()
         //TODO: set /home folder as root
@@ -25,8 +117,208 @@ async fn pick_folder() -> Option<PathBuf> {
     Some(path)
 }
 
-#[derive(Debug)]
-struct Profile {
+/// Runs `parser` on its own thread, yielding a `ParseProgressed` for each of
+/// its reports and then the result.
+#[cfg(feature = "gui")]
+fn parse_with_progress(mut parser: TanitaParser) -> impl Stream<Item = Message> {
+    let (tx, rx) = iced::futures::channel::mpsc::unbounded();
+    let (progress_tx, progress_rx) = std::sync::mpsc::channel();
+    parser.progress = Some(progress_tx);
+    let progress = tx.clone();
+    // Ends once the parser, and with it the sender, is dropped.
+    thread::spawn(move || {
+        for report in progress_rx {
+            let _ = progress.unbounded_send(Message::ParseProgressed(report));
+        }
+    });
+    thread::spawn(move || {
+        let root = parser.root_dir.clone();
+        let message = match parser.get_raw_users_records() {
+            Err(TanitaValidationError::Cancelled) => Message::ParseCancelled,
+            raw => Message::Parsed(root, raw.map_err(|err| err.to_string())),
+        };
+        drop(parser);
+        let _ = tx.unbounded_send(message);
+    });
+    rx
+}
+
+/// Asks for a dump saved with "Save debug dump" and reads it, off the UI thread.
+/// `None` when the dialog was cancelled.
+#[cfg(feature = "gui")]
+async fn read_dump() -> Option<Result<String, String>> {
+    let file_handle = AsyncFileDialog::new()
+        .set_title("Open a saved dump")
+        .add_filter("JSON", &["json"])
+        .pick_file()
+        .await?;
+    let path: PathBuf = file_handle.into();
+    Some(std::fs::read_to_string(&path).map_err(|err| format!("{}: {}", path.display(), err)))
+}
+
+/// Asks for a parent folder and lists every Tanita folder below it, off the UI thread.
+/// `None` when the dialog was cancelled.
+#[cfg(feature = "gui")]
+async fn scan_for_tanita_folders() -> Option<Vec<PathBuf>> {
+    let root: PathBuf = AsyncFileDialog::new()
+        .set_title("Pick a folder holding several Tanita dumps")
+        .pick_folder()
+        .await?
+        .into();
+    let (tx, rx) = iced::futures::channel::oneshot::channel();
+    thread::spawn(move || {
+        let _ = tx.send(find_tanita_folders(&root));
+    });
+    rx.await.ok()
+}
+
+#[cfg(feature = "gui")]
+const APP_TITLE: &str = "Tanita BC-601 viewer";
+
+/// Folder structure on the scale's SD card, shown on the empty screen.
+#[cfg(feature = "gui")]
+const CARD_LAYOUT: &str = "\
+SD card
+└── TANITA
+    └── GRAPHV1        <- choose this folder
+        ├── DATA
+        │   ├── DATA1.CSV
+        │   └── ...
+        └── SYSTEM
+            ├── PROF1.CSV
+            └── ...";
+
+/// Pure black and white with a yellow accent, well above WCAG AAA contrast.
+#[cfg(feature = "gui")]
+static HIGH_CONTRAST_THEME: LazyLock<Theme> = LazyLock::new(|| {
+    Theme::custom(
+        "High contrast".to_string(),
+        iced::theme::Palette {
+            background: iced::Color::BLACK,
+            text: iced::Color::WHITE,
+            primary: iced::Color::from_rgb(1.0, 0.85, 0.0),
+            success: iced::Color::from_rgb(0.0, 1.0, 0.4),
+            danger: iced::Color::from_rgb(1.0, 0.3, 0.3),
+        },
+    )
+});
+
+/// Typing pause after which the search box is applied to the table.
+#[cfg(feature = "gui")]
+const SEARCH_DEBOUNCE: Duration = Duration::from_millis(150);
+
+/// Quiet period after the last change before settings and edits are written,
+/// so a burst of edits (or a dragged slider) is saved once.
+#[cfg(feature = "gui")]
+const AUTOSAVE_DEBOUNCE: Duration = Duration::from_millis(500);
+
+/// Resolves after `delay` without holding up an executor thread. All timers
+/// share async-io's single driver thread.
+#[cfg(feature = "gui")]
+async fn sleep(delay: Duration) {
+    async_io::Timer::after(delay).await;
+}
+
+/// `message` after `delay`. Dropping the handle aborts the timer, so keeping
+/// only the latest one debounces a burst of events.
+#[cfg(feature = "gui")]
+fn delayed(delay: Duration, message: Message) -> (Task<Message>, task::Handle) {
+    let (task, handle) = Task::perform(sleep(delay), move |_| message.clone()).abortable();
+    (task, handle.abort_on_drop())
+}
+
+/// How often the card mount path is checked.
+#[cfg(feature = "gui")]
+const CARD_POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Yields the Tanita folder on the card at `mount` each time a card is inserted,
+/// once it was found on two polls in a row so a half-mounted card is skipped.
+#[cfg(feature = "gui")]
+fn watch_card(mount: PathBuf) -> impl Stream<Item = PathBuf> {
+    iced::futures::stream::unfold(
+        (mount, 0u8, false),
+        |(mount, mut seen, mut reported)| async move {
+            loop {
+                sleep(CARD_POLL_INTERVAL).await;
+                // File system calls on a slow or hung card would stall the executor.
+                let probe = mount.clone();
+                match blocking::unblock(move || find_card_folder(&probe)).await {
+                    Some(folder) => {
+                        seen = seen.saturating_add(1);
+                        if seen >= 2 && !reported {
+                            return Some((folder, (mount, seen, true)));
+                        }
+                    }
+                    None => {
+                        seen = 0;
+                        reported = false;
+                    }
+                }
+            }
+        },
+    )
+}
+
+/// Files handed out by xdg-desktop-portal live under `/run/user/<uid>/doc/`.
+#[cfg(feature = "gui")]
+fn is_document_portal_path(path: &Path) -> bool {
+    path.starts_with("/run/user")
+        && path.components().nth(4) == Some(Component::Normal("doc".as_ref()))
+}
+
+/// Checks the folder right away, so a vanished portal mount is not reported
+/// later as a missing DATA folder.
+#[cfg(feature = "gui")]
+fn check_picked_path(path: &Path) -> Result<(), String> {
+    if path.is_dir() {
+        return Ok(());
+    }
+    if is_document_portal_path(path) {
+        return Err(format!(
+            "{} is a desktop portal mount that is no longer accessible. \
+             Allow the app to read the Tanita folder directly (for Flatpak: \
+             flatpak override --filesystem=<folder>) and pick it again",
+            path.display()
+        ));
+    }
+    Err(format!(
+        "{} does not exist or is not a folder",
+        path.display()
+    ))
+}
+
+/// Tags `Measurement::to_map` skips as the measurement doesn't keep them.
+const NOT_IN_MAP: [FieldTag; 5] = [
+    FieldTag::Model,
+    FieldTag::BirthDate,
+    FieldTag::Date,
+    FieldTag::Time,
+    FieldTag::Checksum,
+];
+
+/// Shown for a value the measurement doesn't have.
+const MISSING_VALUE: &str = "-";
+
+/// No one is this tall in cm, so a smaller `Hm` was written in meters.
+const MAX_HEIGHT_IN_METERS: f32 = 3.0;
+
+/// `Hm` in centimeters. Some profile exports store meters (1.75 rather
+/// than 175); those are scaled up so BMI recomputations stay sane. The
+/// parser reports each one as `WarningKind::HeightInMeters`.
+fn height_in_cm(height: f32) -> f32 {
+    if height > 0.0 && height < MAX_HEIGHT_IN_METERS {
+        height * 100.0
+    } else {
+        height
+    }
+}
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Profile {
+    /// Missing from dumps written before it was kept.
+    #[cfg_attr(feature = "serde", serde(default))]
+    model: DeviceModel,
     birth_date_dmy: Date,
     gender: Gender,
     height_cm: f32,
@@ -35,83 +327,150 @@ struct Profile {
 }
 
 impl Profile {
+    pub fn birth_date(&self) -> &Date {
+        &self.birth_date_dmy
+    }
+
+    pub fn gender(&self) -> &Gender {
+        &self.gender
+    }
+
+    pub fn height_cm(&self) -> f32 {
+        self.height_cm
+    }
+
+    pub fn activity_level(&self) -> ActivityLevel {
+        ActivityLevel::from(self.activity_level_code)
+    }
+
+    pub fn body_type(&self) -> BodyType {
+        BodyType::from(self.body_type_code)
+    }
+
+    pub fn model(&self) -> &DeviceModel {
+        &self.model
+    }
+
     fn from_raw(raw: ProfRaw) -> Option<Profile> {
         let date = Date::from_string(&raw.birth_date_dmy)?;
-
         Some(Profile {
+            model: DeviceModel::from(raw.model.as_str()),
             birth_date_dmy: date,
             body_type_code: raw.body_type_code,
             activity_level_code: raw.activity_level_code,
-            height_cm: raw.height_cm,
+            height_cm: height_in_cm(raw.height_cm),
             gender: Gender::from(raw.gender_code),
         })
     }
 }
 
+/// Ordered and compared by `Measurement::by_date`, so two measurements are
+/// equal when they share a timestamp and a source line.
 #[derive(Debug)]
-struct Measurement {
-    date_time: DateTime,
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Measurement {
+    /// `None` when the scale's clock was unset (placeholder `00/00/0000` date).
+    date_time: Option<DateTime>,
+    source_line: usize,
+    /// Original DATA line, when the parser was asked to keep it.
+    raw_row: Option<String>,
 
-    //  gender_code: Gender,
+    // profile state echoed at measurement time
+    gender: Gender,
     age_years: u8,
-    //  height_cm: f32,
+    height_cm: f32,
     activity_level_code: u8,
     body_type_code: u8,
 
     // body metrics
     weight_kg: f32,
     bmi: f32,
-    fat_percent: f32,
+    fat_percent: Percent,
 
     // segmental fat
-    fat_right_arm_pct: f32,
-    fat_left_arm_pct: f32,
-    fat_right_leg_pct: f32,
-    fat_left_leg_pct: f32,
-    fat_trunk_pct: f32,
+    fat_right_arm_pct: Option<Percent>,
+    fat_left_arm_pct: Option<Percent>,
+    fat_right_leg_pct: Option<Percent>,
+    fat_left_leg_pct: Option<Percent>,
+    fat_trunk_pct: Option<Percent>,
 
     // optional extras
-    muscle_percent: Option<f32>,
-    muscle_right_arm_pct: Option<f32>,
-    muscle_left_arm_pct: Option<f32>,
-    muscle_right_leg_pct: Option<f32>,
-    muscle_left_leg_pct: Option<f32>,
-    muscle_trunk_pct: Option<f32>,
+    muscle_percent: Option<Percent>,
+    muscle_right_arm_pct: Option<Percent>,
+    muscle_left_arm_pct: Option<Percent>,
+    muscle_right_leg_pct: Option<Percent>,
+    muscle_left_leg_pct: Option<Percent>,
+    muscle_trunk_pct: Option<Percent>,
 
     bone_kg: Option<f32>,
-    water_percent: Option<f32>,
+    water_percent: Option<Percent>,
     visceral_fat_rating: Option<u8>,
     metabolic_age_years: Option<u8>,
     daily_calorie_intake_kcal: Option<u16>,
+
+    phase_angle: Option<f32>,
+    impedance_ohms: Option<f32>,
+
+    /// Tags the parser did not recognise, as `(key, value)`.
+    extras: Vec<(String, String)>,
+    /// Values of this row that didn't read as numbers and were taken as 0.
+    parse_warnings: Vec<ParseWarning>,
+
+    /// Echoed gender or height disagrees with the profile, so someone else may
+    /// have stepped on this user's profile. Kept, only flagged.
+    possibly_wrong_user: bool,
+    /// The row's `CS` didn't match, so its values may be corrupt. Kept, only flagged.
+    checksum_ok: bool,
+    /// Hand-excluded (e.g. a misread); still listed but left out of charts and stats.
+    excluded: bool,
+    /// Starred by hand; marked on the chart and exported on its own.
+    milestone: bool,
+    /// Device values of fields that were overridden by hand.
+    original: BTreeMap<Field, Option<f32>>,
 }
 
 impl Measurement {
     fn from_raw(raw: DataRaw) -> Option<Measurement> {
-        let date_time = DateTime::from_string(&raw.date_dmy, &raw.time_hms)?;
+        let date_time = if Date::is_unset_placeholder(&raw.date_dmy) {
+            None
+        } else {
+            Some(DateTime::from_string(&raw.date_dmy, &raw.time_hms)?)
+        };
 
         Some(Measurement {
-            // gender_code: Gender::from(raw.gender_code),
+            source_line: raw.source_line,
+            raw_row: raw.raw_row,
+            gender: Gender::from(raw.gender_code),
             date_time,
-            // height_cm: raw.height_cm,
+            height_cm: height_in_cm(raw.height_cm),
             activity_level_code: raw.activity_level_code,
             body_type_code: raw.body_type_code,
             daily_calorie_intake_kcal: raw.daily_calorie_intake_kcal,
+            phase_angle: raw.phase_angle,
+            impedance_ohms: raw.impedance_ohms,
+            extras: raw.extras,
+            parse_warnings: raw.parse_warnings,
+            possibly_wrong_user: false,
+            checksum_ok: raw.checksum_ok,
+            excluded: false,
+            milestone: false,
+            original: BTreeMap::new(),
             metabolic_age_years: raw.metabolic_age_years,
             visceral_fat_rating: raw.visceral_fat_rating,
-            water_percent: raw.water_percent,
+            water_percent: raw.water_percent.map(Percent::new),
             bone_kg: raw.bone_kg,
-            muscle_trunk_pct: raw.muscle_trunk_pct,
-            muscle_left_leg_pct: raw.muscle_left_leg_pct,
-            muscle_right_leg_pct: raw.muscle_right_leg_pct,
-            muscle_right_arm_pct: raw.muscle_right_arm_pct,
-            muscle_left_arm_pct: raw.muscle_left_arm_pct,
-            muscle_percent: raw.muscle_percent,
-            fat_trunk_pct: raw.fat_trunk_pct,
-            fat_left_leg_pct: raw.fat_left_leg_pct,
-            fat_right_leg_pct: raw.fat_right_leg_pct,
-            fat_left_arm_pct: raw.fat_left_arm_pct,
-            fat_right_arm_pct: raw.fat_right_arm_pct,
-            fat_percent: raw.fat_percent,
+            muscle_trunk_pct: raw.muscle_trunk_pct.map(Percent::new),
+            muscle_left_leg_pct: raw.muscle_left_leg_pct.map(Percent::new),
+            muscle_right_leg_pct: raw.muscle_right_leg_pct.map(Percent::new),
+            muscle_right_arm_pct: raw.muscle_right_arm_pct.map(Percent::new),
+            muscle_left_arm_pct: raw.muscle_left_arm_pct.map(Percent::new),
+            muscle_percent: raw.muscle_percent.map(Percent::new),
+            fat_trunk_pct: raw.fat_trunk_pct.map(Percent::new),
+            fat_left_leg_pct: raw.fat_left_leg_pct.map(Percent::new),
+            fat_right_leg_pct: raw.fat_right_leg_pct.map(Percent::new),
+            fat_left_arm_pct: raw.fat_left_arm_pct.map(Percent::new),
+            fat_right_arm_pct: raw.fat_right_arm_pct.map(Percent::new),
+            fat_percent: Percent::new(raw.fat_percent),
             bmi: raw.bmi,
             weight_kg: raw.weight_kg,
             age_years: raw.age_years,
@@ -119,247 +478,2312 @@ impl Measurement {
     }
 }
 
+impl Measurement {
+    /// `None` for a reading taken before the scale's clock was set.
+    pub fn date_time(&self) -> Option<&DateTime> {
+        self.date_time.as_ref()
+    }
+
+    /// Original DATA line, when the parser was asked to keep it.
+    pub fn raw_row(&self) -> Option<&str> {
+        self.raw_row.as_deref()
+    }
+
+    /// Values of this row that didn't read as numbers and were taken as 0.
+    pub fn parse_warnings(&self) -> &[ParseWarning] {
+        &self.parse_warnings
+    }
+
+    /// The age to show: the scale's echoed `AG`, or, with `prefer_computed`,
+    /// the age worked out from `birth` when the two differ by more than a year
+    /// (the echo often lags behind a profile that was never updated).
+    /// `None` when neither is known.
+    #[cfg(feature = "gui")]
+    fn effective_age(&self, birth: &Date, prefer_computed: bool) -> Option<u16> {
+        let echoed = (self.age_years > 0).then_some(u16::from(self.age_years));
+        let computed = self
+            .date_time
+            .as_ref()
+            .and_then(|date_time| birth.years_until(date_time.date()));
+        match (echoed, computed) {
+            (Some(echoed), Some(computed)) if prefer_computed && echoed.abs_diff(computed) > 1 => {
+                Some(computed)
+            }
+            (None, Some(computed)) if prefer_computed => Some(computed),
+            _ => echoed,
+        }
+    }
+
+    /// Value of an unrecognised tag, quotes removed; the first one if the row repeats it.
+    pub fn extra(&self, key: &str) -> Option<&str> {
+        parser::find_extra(&self.extras, key)
+    }
+
+    /// `extra` read as a number, `None` when absent or not numeric.
+    ///
+    /// ```
+    /// use tanita::model::UserMeasurements;
+    /// use tanita::parser::{DataRaw, ParserConfig, ProfRaw, RawUserRecord};
+    ///
+    /// let row = r#"MO,"BC-601",DT,"05/01/2020",Ti,"09:07:00",Wk,80.0,Zx,12.5,CS,02"#;
+    /// let user = UserMeasurements::from_raw(RawUserRecord {
+    ///     index: 0,
+    ///     profiles: vec![ProfRaw::from_csv_row(r#"MO,"BC-601",DB,"14/06/1991",GE,1,Hm,175"#)],
+    ///     data: vec![DataRaw::from_csv_row(row, &ParserConfig::default())],
+    ///     data_file_empty: false,
+    /// })
+    /// .unwrap();
+    /// assert_eq!(user.measurements()[0].extra_f32("Zx"), Some(12.5));
+    /// ```
+    pub fn extra_f32(&self, key: &str) -> Option<f32> {
+        self.extra(key)?.parse().ok()
+    }
+
+    /// Stable ID of the measurement within its user, used to key hand edits
+    /// and anything kept about it outside the app.
+    /// Hashes the timestamp and the device weight (an override doesn't change
+    /// it), so it is the same on every reload of the same DATA file. Undated
+    /// rows use their line number instead of the timestamp. FNV-1a rather than
+    /// std's hasher, whose output may change between Rust releases.
+    pub fn fingerprint(&self) -> u64 {
+        const FNV_OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+        const FNV_PRIME: u64 = 0x0000_0100_0000_01b3;
+
+        let weight_kg = match self.original.get(&Field::WeightKg) {
+            Some(Some(device_value)) => *device_value,
+            _ => self.weight_kg,
+        };
+        let time_key = match &self.date_time {
+            Some(date_time) => date_time.seconds_since_epoch(),
+            None => -1 - self.source_line as i64,
+        };
+        time_key
+            .to_le_bytes()
+            .into_iter()
+            .chain(weight_kg.to_bits().to_le_bytes())
+            .fold(FNV_OFFSET_BASIS, |hash, byte| {
+                (hash ^ u64::from(byte)).wrapping_mul(FNV_PRIME)
+            })
+    }
+
+    /// Chronological order, and `Measurement`'s `Ord`: by `date_time`, then by
+    /// `source_line`, so measurements sharing a timestamp keep the order they had
+    /// in the DATA file. Measurements taken before the clock was set have no
+    /// timestamp and sort first.
+    pub fn by_date(&self, other: &Measurement) -> std::cmp::Ordering {
+        self.date_time
+            .cmp(&other.date_time)
+            .then(self.source_line.cmp(&other.source_line))
+    }
+
+    #[cfg(feature = "gui")]
+    fn clock_unset(&self) -> bool {
+        self.date_time.is_none()
+    }
+
+    /// Any muscle reading, overall or segmental.
+    #[cfg(feature = "gui")]
+    fn has_muscle_data(&self) -> bool {
+        [
+            self.muscle_percent,
+            self.muscle_trunk_pct,
+            self.muscle_right_arm_pct,
+            self.muscle_left_arm_pct,
+            self.muscle_right_leg_pct,
+            self.muscle_left_leg_pct,
+        ]
+        .iter()
+        .any(Option::is_some)
+    }
+
+    /// The labels of `to_map`, in the same order, without needing a measurement.
+    pub fn map_labels(units: Units) -> Vec<Cow<'static, str>> {
+        std::iter::once(Cow::Borrowed("Date and time"))
+            .chain(
+                FieldTag::ALL
+                    .into_iter()
+                    .filter(|tag| !NOT_IN_MAP.contains(tag))
+                    .map(|tag| units.label(tag)),
+            )
+            .chain([Cow::Borrowed("Phase angle"), Cow::Borrowed("Impedance")])
+            .collect()
+    }
+
+    /// Label → plain formatted value for every field kept on the measurement,
+    /// in `FieldTag` order. Missing optional values are `-`.
+    /// Mass and length fields are given in `units`, with the unit in their label.
+    /// Missing values read `MISSING_VALUE`.
+    pub fn to_map(&self, units: Units) -> Vec<(Cow<'static, str>, String)> {
+        fn opt<T: ToString>(value: Option<T>) -> String {
+            value.map_or_else(|| MISSING_VALUE.to_string(), |v| v.to_string())
+        }
+        fn pct(value: Option<Percent>) -> String {
+            opt(value.map(|p| p.value()))
+        }
+
+        let mut map = vec![(Cow::Borrowed("Date and time"), opt(self.date_time.as_ref()))];
+        for tag in FieldTag::ALL {
+            let value = match tag {
+                FieldTag::Gender => self.gender.to_string(),
+                FieldTag::Age => self.age_years.to_string(),
+                FieldTag::Height => units.convert(tag, self.height_cm).to_string(),
+                FieldTag::ActivityLevel => self.activity_level_code.to_string(),
+                FieldTag::BodyType => self.body_type_code.to_string(),
+                FieldTag::Weight => units.convert(tag, self.weight_kg).to_string(),
+                FieldTag::Bmi => self.bmi.to_string(),
+                FieldTag::FatPercent => pct(Some(self.fat_percent)),
+                FieldTag::FatRightArm => pct(self.fat_right_arm_pct),
+                FieldTag::FatLeftArm => pct(self.fat_left_arm_pct),
+                FieldTag::FatRightLeg => pct(self.fat_right_leg_pct),
+                FieldTag::FatLeftLeg => pct(self.fat_left_leg_pct),
+                FieldTag::FatTrunk => pct(self.fat_trunk_pct),
+                FieldTag::MusclePercent => pct(self.muscle_percent),
+                FieldTag::MuscleRightArm => pct(self.muscle_right_arm_pct),
+                FieldTag::MuscleLeftArm => pct(self.muscle_left_arm_pct),
+                FieldTag::MuscleRightLeg => pct(self.muscle_right_leg_pct),
+                FieldTag::MuscleLeftLeg => pct(self.muscle_left_leg_pct),
+                FieldTag::MuscleTrunk => pct(self.muscle_trunk_pct),
+                FieldTag::BoneMass => opt(self.bone_kg.map(|kg| units.convert(tag, kg))),
+                FieldTag::WaterPercent => pct(self.water_percent),
+                FieldTag::VisceralFat => opt(self.visceral_fat_rating),
+                FieldTag::MetabolicAge => opt(self.metabolic_age_years),
+                FieldTag::DailyCalorieIntake => opt(self.daily_calorie_intake_kcal),
+                // not kept on `Measurement`, see `NOT_IN_MAP`
+                FieldTag::Model
+                | FieldTag::BirthDate
+                | FieldTag::Date
+                | FieldTag::Time
+                | FieldTag::Checksum => continue,
+            };
+            map.push((units.label(tag), value));
+        }
+        map.push((Cow::Borrowed("Phase angle"), opt(self.phase_angle)));
+        map.push((Cow::Borrowed("Impedance"), opt(self.impedance_ohms)));
+        map
+    }
+}
+
+impl PartialEq for Measurement {
+    fn eq(&self, other: &Self) -> bool {
+        self.by_date(other).is_eq()
+    }
+}
+
+impl Eq for Measurement {}
+
+impl PartialOrd for Measurement {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Measurement {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        self.by_date(other)
+    }
+}
+
 #[derive(Debug)]
-struct UserMeasurements {
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct UserMeasurements {
     /// Pair index N (from filenames DATA{N}.CSV / PROF{N}.CSV).
     index: usize,
-    /// Parsed, interpreted profile.
+    /// Parsed, interpreted profile: the selected entry of `profile_revisions`.
     profile: Profile,
+    /// Every revision found in PROF{N}.CSV, oldest first.
+    profile_revisions: Vec<Profile>,
+    /// Index into `profile_revisions`, the latest unless picked otherwise.
+    selected_revision: usize,
     /// All measurements parsed from DATA{N}.CSV.
     measurements: Vec<Measurement>,
+    /// DATA{N}.CSV had no rows at all, as opposed to rows that failed to parse.
+    data_file_empty: bool,
+}
+
+/// Each user paired with its index, so users can be handled, exported or moved
+/// to another thread on their own. `from_per_user` puts them back together.
+pub fn into_per_user(users: Vec<UserMeasurements>) -> Vec<(usize, UserMeasurements)> {
+    users.into_iter().map(|u| (u.index, u)).collect()
+}
+
+/// One dataset again, ordered by index as the parser returns it.
+pub fn from_per_user(
+    users: impl IntoIterator<Item = (usize, UserMeasurements)>,
+) -> Vec<UserMeasurements> {
+    let mut users: Vec<(usize, UserMeasurements)> = users.into_iter().collect();
+    users.sort_by_key(|(index, _)| *index);
+    users.into_iter().map(|(_, u)| u).collect()
 }
 
+/// Echoed heights further than this from the profile's count as a mismatch.
+/// Loose on purpose: the profile holds the current height and children grow.
+const HEIGHT_MISMATCH_CM: f32 = 3.0;
+
 impl UserMeasurements {
-    fn from_raw(raw: RawUserRecord) -> UserMeasurements {
-        let profile = Profile::from_raw(raw.profile).unwrap();
+    /// Takes over `other`'s measurements, keeping them sorted. `other`'s profile is dropped.
+    #[cfg(feature = "gui")]
+    fn absorb(&mut self, mut other: UserMeasurements) {
+        self.measurements.append(&mut other.measurements);
+        self.measurements.sort();
+        self.data_file_empty &= other.data_file_empty;
+    }
+
+    /// A user built from parts, e.g. measurements moved over from another user.
+    /// `profile` is the only revision; measurements are kept in the given order.
+    pub fn new(index: usize, profile: Profile, measurements: Vec<Measurement>) -> UserMeasurements {
+        let mut user = UserMeasurements {
+            index,
+            profile: profile.clone(),
+            profile_revisions: vec![profile],
+            selected_revision: 0,
+            data_file_empty: measurements.is_empty(),
+            measurements,
+        };
+        user.flag_wrong_user();
+        user
+    }
+
+    /// The index, selected profile and measurements, taken apart without copying.
+    pub fn into_parts(self) -> (usize, Profile, Vec<Measurement>) {
+        (self.index, self.profile, self.measurements)
+    }
+
+    /// Pair index N of DATA{N}.CSV / PROF{N}.CSV, counted from 0.
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    /// The latest profile revision, unless another one was picked.
+    pub fn profile(&self) -> &Profile {
+        &self.profile
+    }
+
+    /// In DATA file order, with hand edits applied when loaded by the app.
+    pub fn measurements(&self) -> &[Measurement] {
+        &self.measurements
+    }
+
+    /// Every profile revision found in PROF{N}.CSV, oldest first.
+    pub fn profile_revisions(&self) -> &[Profile] {
+        &self.profile_revisions
+    }
+
+    /// Index into `profile_revisions` of `profile`.
+    pub fn selected_revision(&self) -> usize {
+        self.selected_revision
+    }
+
+    /// DATA{N}.CSV had no rows at all, as opposed to rows that failed to parse.
+    pub fn data_file_empty(&self) -> bool {
+        self.data_file_empty
+    }
+
+    /// `None` when no line of the PROF file has a readable birth date.
+    pub fn from_raw(raw: RawUserRecord) -> Option<UserMeasurements> {
+        let profile_revisions: Vec<Profile> = raw
+            .profiles
+            .into_iter()
+            .filter_map(Profile::from_raw)
+            .collect();
+        let selected_revision = profile_revisions.len().checked_sub(1)?;
+        let profile = profile_revisions[selected_revision].clone();
         let mut measurements: Vec<Measurement> = Vec::with_capacity(raw.data.len());
+        // Rows without a valid timestamp are left out; the parser reported them
+        // as `WarningKind::InvalidTimestamp`.
         for data in raw.data {
-            let m = Measurement::from_raw(data);
-            match m {
-                Some(m) => {
-                    measurements.push(m);
-                }
-                None => {
-                    println!("Measurement is missing")
-                }
+            if let Some(m) = Measurement::from_raw(data) {
+                measurements.push(m);
             }
         }
-        UserMeasurements {
+        let mut user = UserMeasurements {
             index: raw.index,
             profile,
+            profile_revisions,
+            selected_revision,
             measurements,
+            data_file_empty: raw.data_file_empty,
+        };
+        user.flag_wrong_user();
+        Some(user)
+    }
+
+    /// Marks measurements whose echoed gender or height disagree with `profile`.
+    fn flag_wrong_user(&mut self) {
+        for m in &mut self.measurements {
+            let gender_differs = m.gender != Gender::Other(0) && m.gender != self.profile.gender;
+            let height_differs = m.height_cm > 0.0
+                && (m.height_cm - self.profile.height_cm).abs() > HEIGHT_MISMATCH_CM;
+            m.possibly_wrong_user = gender_differs || height_differs;
+        }
+    }
+
+    /// Makes another profile revision the current one; out of range is ignored.
+    #[cfg(feature = "gui")]
+    fn select_profile_revision(&mut self, revision: usize) {
+        let Some(profile) = self.profile_revisions.get(revision) else {
+            return;
+        };
+        self.profile = profile.clone();
+        self.selected_revision = revision;
+        self.flag_wrong_user();
+    }
+
+    /// Index of the earliest not excluded measurement with muscle data: where
+    /// the history from a muscle-measuring device starts.
+    #[cfg(feature = "gui")]
+    fn first_full_featured_index(&self) -> Option<usize> {
+        self.measurements
+            .iter()
+            .enumerate()
+            .filter(|(_, m)| !m.excluded && m.muscle_percent.is_some())
+            .min_by_key(|(_, m)| *m)
+            .map(|(i, _)| i)
+    }
+
+    /// Measurements passing `filter`, with their index, in stored order.
+    #[cfg(feature = "gui")]
+    fn in_view(&self, filter: ViewFilter) -> impl Iterator<Item = (usize, &Measurement)> {
+        let era_start = filter
+            .full_feature_era_only
+            .then(|| self.first_full_featured_index())
+            .flatten()
+            .map(|i| &self.measurements[i]);
+        self.measurements
+            .iter()
+            .enumerate()
+            .filter(move |(_, m)| !filter.muscle_only || m.muscle_percent.is_some())
+            .filter(move |(_, m)| era_start.is_none_or(|start| *m >= start))
+    }
+
+    /// Measurements not excluded by hand that pass `filter`, oldest first.
+    #[cfg(feature = "gui")]
+    fn charted(&self, filter: ViewFilter) -> Vec<&Measurement> {
+        let mut by_date: Vec<&Measurement> = self
+            .in_view(filter)
+            .map(|(_, m)| m)
+            .filter(|m| !m.excluded)
+            .collect();
+        by_date.sort();
+        by_date
+    }
+
+    /// Starred measurements not excluded by hand, oldest first.
+    pub fn milestones(&self) -> Vec<&Measurement> {
+        let mut milestones: Vec<&Measurement> = self
+            .measurements
+            .iter()
+            .filter(|m| m.milestone && !m.excluded)
+            .collect();
+        milestones.sort();
+        milestones
+    }
+
+    /// `metric` of every dated measurement not excluded by hand that carries it, oldest first.
+    #[cfg(feature = "gui")]
+    fn series(&self, metric: Metric, filter: ViewFilter) -> Vec<(DateTime, f32)> {
+        self.charted(filter)
+            .into_iter()
+            .filter_map(|m| Some((m.date_time.clone()?, metric.value(m)?)))
+            .collect()
+    }
+
+    /// Weight of every dated measurement not excluded by hand that passes `filter`, oldest first.
+    #[cfg(feature = "gui")]
+    fn weights_chronological(&self, filter: ViewFilter) -> Vec<(DateTime, f32)> {
+        self.series(Metric::WeightKg, filter)
+    }
+
+    #[cfg(feature = "gui")]
+    fn latest_measurement(&self) -> Option<&DateTime> {
+        self.measurements
+            .iter()
+            .filter_map(|m| m.date_time.as_ref())
+            .max()
+    }
+}
+
+/// Which measurements the table, charts and sparklines show.
+#[cfg(feature = "gui")]
+#[derive(Debug, Clone, Copy, Default)]
+struct ViewFilter {
+    /// Only measurements with muscle data.
+    muscle_only: bool,
+    /// Only measurements from the first one with muscle data onwards, so the
+    /// history of an older fat-only scale is left out. No effect without muscle data.
+    full_feature_era_only: bool,
+}
+
+/// Order in which user tabs are shown.
+#[cfg(feature = "gui")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+enum UserSort {
+    /// Pair index N, as the files are numbered on the card.
+    #[default]
+    Index,
+    /// Most recently measured user first; users without measurements go last.
+    LatestMeasurement,
+}
+
+#[cfg(feature = "gui")]
+impl UserSort {
+    const ALL: [UserSort; 2] = [UserSort::Index, UserSort::LatestMeasurement];
+
+    fn sort(&self, users: &mut [&UserMeasurements]) {
+        match self {
+            UserSort::Index => users.sort_by_key(|u| u.index),
+            UserSort::LatestMeasurement => {
+                users.sort_by(|a, b| b.latest_measurement().cmp(&a.latest_measurement()))
+            }
+        }
+    }
+}
+
+#[cfg(feature = "gui")]
+impl fmt::Display for UserSort {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            UserSort::Index => write!(f, "By index"),
+            UserSort::LatestMeasurement => write!(f, "By latest measurement"),
         }
     }
 }
 
+const _: () = {
+    fn assert_send_sync<T: Send + Sync>() {}
+    let _ = assert_send_sync::<Profile>;
+    let _ = assert_send_sync::<Measurement>;
+    let _ = assert_send_sync::<UserMeasurements>;
+};
+
+#[cfg(feature = "gui")]
 #[derive(Debug, Clone)]
 enum Message {
     PickFileOrFolder,
     PathPicked(Option<PathBuf>),
+    ScanDirectory,
+    OpenDump,
+    /// Contents of the picked dump, or why it couldn't be read; `None` if cancelled.
+    DumpRead(Option<Result<String, String>>),
+    /// A card with a Tanita folder showed up under `settings.card_mount_path`.
+    CardDetected(PathBuf),
+    DismissCard,
+    FoldersScanned(Option<Vec<PathBuf>>),
+    /// The picked folder and what was read from it, or why it couldn't be.
+    ParseProgressed(ParseProgress),
+    Parsed(PathBuf, Result<ParsedDataset, String>),
+    /// A parse stopped by `ParseJob::cancel`; nothing changes.
+    ParseCancelled,
+    CancelParse,
     TabSelected(usize),
+    SetUserSort(UserSort),
+    SetNumberFormat(NumberFormat),
+    ToggleProfileColumns(bool),
+    ToggleCompact(bool),
+    ToggleKeepRawRows(bool),
+    /// Device-reported (`false`) or recomputed (`true`) age and BMI.
+    ToggleValueSource(bool),
+    ToggleMuscleOnly(bool),
+    ToggleFullFeatureEra(bool),
+    SetChartMetric(Metric),
+    ToggleHighContrast(bool),
+    ToggleFatDistribution(bool),
+    /// Oldest (`false`) or newest (`true`) measurement first in the table.
+    ToggleSortOrder(bool),
+    /// Metric (`false`) or imperial (`true`) units in the table.
+    ToggleUnits(bool),
+    SetFontScale(f32),
+    /// Writes pending settings and edits if nothing changed since it was scheduled.
+    AutoSave(u64),
+    SearchChanged(String),
+    /// Applies the search text if no newer keystroke arrived; carries the generation it was scheduled for.
+    ApplySearch(u64),
+    /// User index and `Measurement::fingerprint`, as are the edit messages below.
+    EditMeasurement(usize, u64),
+    EditorFieldSelected(Field),
+    EditorValueChanged(String),
+    OverrideMeasurement(usize, u64, Field, Option<f32>),
+    ExcludeMeasurement(usize, u64, bool),
+    ToggleMilestone(usize, u64),
+    CloseEditor,
+    CopyToClipboard(String),
+    SaveUnknownTagReport,
+    SaveParseWarningReport,
+    /// Expands or collapses the list of the loaded dataset's warnings.
+    ToggleDatasetWarnings,
+    /// User index and index into that user's profile revisions.
+    SelectProfileRevision(usize, usize),
+    /// Excludes the readings `likely_test_readings` flags for this user.
+    ExcludeTestReadings(usize),
+    /// User index and the metric to show large on their tab.
+    SetPrimaryMetric(usize, Metric),
+    /// Shows `settings.table_page_size` more of the older rows.
+    LoadMore,
+    /// Folds every listed user into the first one.
+    MergeUsers(Vec<usize>),
+    SetMergeTolerance(MergeTolerance),
+    SetSessionFrom(DateTime),
+    SetSessionTo(DateTime),
+    ExportSession(SessionFormat),
+    ExportUser(ExportPreset),
+    SetExportUnits(Units),
+    ExportMilestones,
+    ExportHouseholdTimeline,
+    /// Saves the household timeline rows newer than `settings.last_sync`.
+    ExportSinceLastSync,
+    /// Save result and the newest timestamp it covered.
+    SyncExported(Result<Option<PathBuf>, String>, Option<DateTime>),
+    /// Saves the loaded dataset as JSON, for bug reports; "Open dump" loads it back.
+    ExportDebugDump,
+    /// Saves the trend chart of the selected user as SVG.
+    ExportChartSvg,
+    FileSaved(Result<Option<PathBuf>, String>),
+}
+
+/// Entry of the profile revision picker.
+#[cfg(feature = "gui")]
+#[derive(Debug, Clone, PartialEq)]
+struct RevisionChoice {
+    index: usize,
+    height_cm: f32,
+    birth_date: String,
+}
+
+#[cfg(feature = "gui")]
+impl fmt::Display for RevisionChoice {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "#{}: {} cm, born {}",
+            self.index + 1,
+            self.height_cm,
+            self.birth_date
+        )
+    }
+}
+
+/// A parse running in the background.
+#[cfg(feature = "gui")]
+struct ParseJob {
+    /// Makes the parser stop at the next row with `TanitaValidationError::Cancelled`.
+    cancel: Arc<AtomicBool>,
+    /// Drops the pending result.
+    handle: task::Handle,
+    /// Last report from the parser, shown while it runs.
+    progress: Option<ParseProgress>,
+}
+
+#[cfg(feature = "gui")]
+impl ParseJob {
+    fn cancel(&self) {
+        self.cancel.store(true, Ordering::Relaxed);
+        self.handle.abort();
+    }
+}
+
+/// State of the inline editor for a single measurement.
+#[cfg(feature = "gui")]
+struct MeasurementEditor {
+    user: usize,
+    fingerprint: u64,
+    field: Field,
+    value: String,
 }
 
+#[cfg(feature = "gui")]
 #[derive(Default)]
 pub struct Application {
+    /// Folder the current dataset was loaded from; the edits sidecar lives here.
+    root_dir: Option<PathBuf>,
+    parsing: Option<ParseJob>,
     measurements: Vec<UserMeasurements>,
+    edits: Edits,
+    editor: Option<MeasurementEditor>,
+    /// Pair index of the selected user (not its position in the tab row).
     selected_tab: usize,
+    /// Bounds of the session comparison; `None` means the last two measurements.
+    session_from: Option<DateTime>,
+    session_to: Option<DateTime>,
+    /// Why the last picked folder could not be opened.
+    load_error: Option<String>,
+    /// Files of the loaded dataset that were ignored for an unreadable user number.
+    skipped_files: Vec<PathBuf>,
+    /// Which user numbers of the loaded dataset lacked a PROF or DATA file.
+    unpaired: Option<String>,
+    /// Files of the loaded dataset ignored for sharing a user number, see `ParsedDataset`.
+    index_collisions: Vec<(PathBuf, Vec<PathBuf>)>,
+    /// What the parser noticed in the loaded dataset, listed on request.
+    dataset_warnings: Vec<DatasetWarning>,
+    show_dataset_warnings: bool,
+    /// Tanita folder on a freshly inserted card, waiting for the user to load it.
+    detected_card: Option<PathBuf>,
+    /// Tanita folders found by the last directory scan, offered for loading.
+    scanned_folders: Vec<PathBuf>,
+    /// Search box contents as typed.
+    search: String,
+    /// Search text the table is filtered by, lags `search` by `SEARCH_DEBOUNCE`.
+    applied_search: String,
+    /// Bumped on every keystroke so an `ApplySearch` already sent by a
+    /// replaced timer is ignored.
+    search_generation: u64,
+    /// Pending `ApplySearch`; replacing it aborts the previous timer.
+    search_timer: Option<task::Handle>,
+    /// Settings or edits changed since they were last written.
+    unsaved_settings: bool,
+    unsaved_edits: bool,
+    /// Bumped on every change so only the last `AutoSave` of a burst writes.
+    save_generation: u64,
+    /// Pending `AutoSave`; replacing it aborts the previous timer.
+    save_timer: Option<task::Handle>,
+    /// Why the last auto-save failed; the changes stay pending and are retried.
+    save_error: Option<String>,
+    /// Outcome of the last action that has no other place on screen, such as
+    /// a saved file or ignored malformed settings.
+    notice: Option<String>,
+    /// Indices into the selected user's measurements that pass the filters,
+    /// in table order. Recomputed by `refresh_filtered` only when one of its inputs changes.
+    filtered: Vec<usize>,
+    /// Weight sparkline of each tab, by user index. Rebuilt by
+    /// `refresh_sparklines` on load, edit or view filter change, not on every redraw.
+    sparklines: BTreeMap<usize, Sparkline>,
+    /// How many of the latest `filtered` rows the table shows; grows by
+    /// `settings.table_page_size` with each "Load more".
+    visible_count: usize,
+    /// Series plotted in the trend chart.
+    chart_metric: Metric,
+    /// Extra table columns added through `register_derived`.
+    derived: Vec<DerivedColumn>,
+    settings: Settings,
 }
 
+#[cfg(feature = "gui")]
 impl Application {
     fn view(&self) -> Column<'_, Message> {
         let mut col = iced::widget::column![].padding(10).spacing(10);
-        if self.measurements.is_empty() {
+        if self.parsing.is_some() {
             col = col.push(
-                button("Choose [GRAPHV1] in a Tanita folder").on_press(Message::PickFileOrFolder),
-            )
+                iced::widget::row![
+                    text(match self.parsing.as_ref().and_then(|job| job.progress) {
+                        Some(p) => format!(
+                            "Reading user {} of {}: {} lines...",
+                            p.user + 1,
+                            p.users,
+                            p.lines
+                        ),
+                        None => "Reading Tanita files...".to_string(),
+                    }),
+                    button("Cancel").on_press(Message::CancelParse)
+                ]
+                .spacing(10)
+                .align_y(iced::Alignment::Center),
+            );
+        } else if self.measurements.is_empty() {
+            col = col.push(
+                iced::widget::row![
+                    button("Choose [GRAPHV1] in a Tanita folder")
+                        .on_press(Message::PickFileOrFolder),
+                    button("Scan directory").on_press(Message::ScanDirectory),
+                    button("Open dump").on_press(Message::OpenDump),
+                    checkbox("Keep raw rows", self.settings.keep_raw_rows)
+                        .on_toggle(Message::ToggleKeepRawRows),
+                ]
+                .spacing(10)
+                .align_y(iced::Alignment::Center),
+            );
+            col = col.push(Self::empty_state());
+        }
+        if let Some(err) = &self.load_error {
+            col = col.push(text(err));
+        }
+        if let Some(err) = &self.save_error {
+            col = col.push(text(format!("Changes not saved yet ({})", err)).style(text::danger));
+        }
+        if let Some(notice) = &self.notice {
+            col = col.push(text(notice));
+        }
+        if let Some(folder) = &self.detected_card {
+            col = col.push(
+                iced::widget::row![
+                    text(format!("Card inserted: {}", folder.display())),
+                    button("Load").on_press(Message::PathPicked(Some(folder.clone()))),
+                    button("Dismiss").on_press(Message::DismissCard),
+                ]
+                .spacing(10)
+                .align_y(iced::Alignment::Center),
+            );
+        }
+        if self.parsing.is_none() && !self.scanned_folders.is_empty() {
+            let mut folders = iced::widget::column![text("Found Tanita folders")].spacing(4);
+            for folder in &self.scanned_folders {
+                folders = folders.push(
+                    button(text(folder.display().to_string()).size(12))
+                        .on_press(Message::PathPicked(Some(folder.clone()))),
+                );
+            }
+            col = col.push(folders);
+        }
+        if let Some(root) = &self.root_dir {
+            col = col.push(text(format!("Reading from {}", root.display())).size(12));
+        }
+        for file in &self.skipped_files {
+            col = col.push(
+                text(TanitaValidationError::MalformedIndex(file.clone()).to_string()).size(12),
+            );
+        }
+        if let Some(unpaired) = &self.unpaired {
+            col = col.push(text(format!("Skipped {}", unpaired)).size(12));
+        }
+        for (used, ignored) in &self.index_collisions {
+            let collision = TanitaValidationError::IndexCollision {
+                used: used.clone(),
+                ignored: ignored.clone(),
+            };
+            col = col.push(text(collision.to_string()).size(12));
+        }
+        if !self.dataset_warnings.is_empty() {
+            let arrow = if self.show_dataset_warnings {
+                "▾"
+            } else {
+                "▸"
+            };
+            col = col.push(
+                button(
+                    text(format!(
+                        "{} {} warning(s)",
+                        arrow,
+                        self.dataset_warnings.len()
+                    ))
+                    .size(12),
+                )
+                .on_press(Message::ToggleDatasetWarnings),
+            );
+            if self.show_dataset_warnings {
+                for warning in &self.dataset_warnings {
+                    col = col.push(text(warning.to_string()).size(12));
+                }
+            }
         }
 
         if !self.measurements.is_empty() {
+            let mut users: Vec<&UserMeasurements> = self.measurements.iter().collect();
+            self.settings.user_sort.sort(&mut users);
+
             let mut tab_titles = iced::widget::row![].spacing(8);
-            for user_mes in &self.measurements {
-                // println!("USER_MES_idx: {}", user_mes.index);
-                tab_titles = tab_titles.push(
-                    button(text(format!("User {}", user_mes.index + 1)))
-                        .on_press(Message::TabSelected(user_mes.index)),
-                );
+            for user_mes in users {
+                let mut title = iced::widget::column![text(format!("User {}", user_mes.index + 1))];
+                if let Some(sparkline) = self.sparklines.get(&user_mes.index) {
+                    title = title.push(
+                        canvas(sparkline)
+                            .width(Length::Fixed(60.0))
+                            .height(Length::Fixed(16.0)),
+                    );
+                }
+                tab_titles =
+                    tab_titles.push(button(title).on_press(Message::TabSelected(user_mes.index)));
             }
+            tab_titles = tab_titles.push(pick_list(
+                UserSort::ALL,
+                Some(self.settings.user_sort),
+                Message::SetUserSort,
+            ));
+            tab_titles = tab_titles.push(pick_list(
+                NumberFormat::ALL,
+                Some(self.settings.number_format),
+                Message::SetNumberFormat,
+            ));
+
+            tab_titles = tab_titles.push(
+                checkbox("Profile per row", self.settings.show_profile_columns)
+                    .on_toggle(Message::ToggleProfileColumns),
+            );
+            tab_titles = tab_titles.push(
+                checkbox("Compact table", self.settings.compact_table)
+                    .on_toggle(Message::ToggleCompact),
+            );
+            tab_titles = tab_titles.push(
+                checkbox("Recomputed values", self.settings.show_recomputed)
+                    .on_toggle(Message::ToggleValueSource),
+            );
+            tab_titles = tab_titles.push(
+                checkbox("Only complete muscle data", self.settings.muscle_only)
+                    .on_toggle(Message::ToggleMuscleOnly),
+            );
+            tab_titles = tab_titles.push(
+                checkbox("Full-feature era only", self.settings.full_feature_era_only)
+                    .on_toggle(Message::ToggleFullFeatureEra),
+            );
+            tab_titles = tab_titles.push(
+                checkbox("Fat distribution", self.settings.show_fat_distribution)
+                    .on_toggle(Message::ToggleFatDistribution),
+            );
+            tab_titles = tab_titles.push(
+                checkbox(
+                    "Imperial units",
+                    self.settings.display_units == Units::Imperial,
+                )
+                .on_toggle(Message::ToggleUnits),
+            );
+            tab_titles = tab_titles.push(
+                checkbox("Newest first", self.settings.newest_first)
+                    .on_toggle(Message::ToggleSortOrder),
+            );
+            tab_titles = tab_titles.push(
+                checkbox("High contrast", self.settings.high_contrast)
+                    .on_toggle(Message::ToggleHighContrast),
+            );
+            tab_titles = tab_titles.push(
+                iced::widget::row![
+                    text("Text size"),
+                    slider(
+                        FONT_SCALE_RANGE,
+                        self.settings.font_scale,
+                        Message::SetFontScale
+                    )
+                    .step(0.05)
+                    .width(Length::Fixed(100.0)),
+                ]
+                .spacing(6)
+                .align_y(iced::Alignment::Center),
+            );
+            tab_titles = tab_titles.push(
+                text_input("Search date", &self.search)
+                    .on_input(Message::SearchChanged)
+                    .width(Length::Fixed(150.0)),
+            );
+            tab_titles = tab_titles.push(
+                button("Export household timeline").on_press(Message::ExportHouseholdTimeline),
+            );
+            tab_titles = tab_titles
+                .push(button("Export new since last sync").on_press(Message::ExportSinceLastSync));
 
             col = col.push(tab_titles);
 
-            let u = &self.measurements[self.selected_tab];
-            col = col.push(TableBuilder::heading(&u.profile));
-            col = col.push(TableBuilder::body(&u.measurements));
+            let mut diagnostics = iced::widget::row![text("Diagnostics")]
+                .spacing(8)
+                .align_y(iced::Alignment::Center);
+            if self
+                .measurements
+                .iter()
+                .any(|u| u.measurements.iter().any(|m| !m.extras.is_empty()))
+            {
+                diagnostics = diagnostics.push(
+                    button("Save unknown tags report").on_press(Message::SaveUnknownTagReport),
+                );
+            }
+            let parse_warnings: usize = self
+                .measurements
+                .iter()
+                .flat_map(|u| &u.measurements)
+                .map(|m| m.parse_warnings.len())
+                .sum();
+            if parse_warnings > 0 {
+                diagnostics = diagnostics.push(
+                    button(text(format!("Save parse warnings ({})", parse_warnings)))
+                        .on_press(Message::SaveParseWarningReport),
+                );
+            }
+            diagnostics =
+                diagnostics.push(button("Save debug dump").on_press(Message::ExportDebugDump));
+            col = col.push(diagnostics);
+
+            let duplicate_groups = find_duplicate_profiles(&self.measurements);
+            if !duplicate_groups.is_empty() {
+                let tolerance = self.settings.merge_tolerance;
+                col = col.push(
+                    iced::widget::row![
+                        text(format!(
+                            "Merging keeps one of any readings within {} s and {} kg of each other",
+                            tolerance.seconds, tolerance.weight_kg
+                        )),
+                        slider(
+                            MERGE_SECONDS_RANGE,
+                            tolerance.seconds as i32,
+                            move |seconds| Message::SetMergeTolerance(MergeTolerance {
+                                seconds: i64::from(seconds),
+                                ..tolerance
+                            })
+                        )
+                        .step(10)
+                        .width(Length::Fixed(100.0)),
+                        slider(
+                            MERGE_WEIGHT_KG_RANGE,
+                            tolerance.weight_kg,
+                            move |weight_kg| {
+                                Message::SetMergeTolerance(MergeTolerance {
+                                    weight_kg,
+                                    ..tolerance
+                                })
+                            }
+                        )
+                        .step(0.05)
+                        .width(Length::Fixed(100.0)),
+                    ]
+                    .spacing(10)
+                    .align_y(iced::Alignment::Center),
+                );
+            }
+            for group in duplicate_groups {
+                let names: Vec<String> = group.iter().map(|i| (i + 1).to_string()).collect();
+                col = col.push(
+                    iced::widget::row![
+                        text(format!(
+                            "Users {} have identical profiles, possibly duplicates",
+                            names.join(", ")
+                        )),
+                        button("Merge").on_press(Message::MergeUsers(group)),
+                    ]
+                    .spacing(10)
+                    .align_y(iced::Alignment::Center),
+                );
+            }
+
+            if let Some(u) = self.selected_user() {
+                let table = TableBuilder {
+                    number_format: self.settings.number_format,
+                    show_profile_columns: self.settings.show_profile_columns,
+                    compact: self.settings.compact_table,
+                    birth_date: u.profile.birth_date_dmy.clone(),
+                    show_recomputed: self.settings.show_recomputed,
+                    font_scale: self.settings.font_scale,
+                    show_fat_distribution: self.settings.show_fat_distribution,
+                    derived: &self.derived,
+                    units: self.settings.display_units,
+                    show_muscle_columns: u.measurements.iter().any(Measurement::has_muscle_data),
+                };
+                let primary = self
+                    .settings
+                    .primary_metrics
+                    .get(&u.index)
+                    .copied()
+                    .unwrap_or_default();
+                let user = u.index;
+                col = col.push(
+                    iced::widget::row![
+                        table.primary_metric(
+                            primary,
+                            &u.series(primary, self.settings.view_filter())
+                        ),
+                        pick_list(Metric::ALL, Some(primary), move |metric| {
+                            Message::SetPrimaryMetric(user, metric)
+                        }),
+                    ]
+                    .spacing(20)
+                    .align_y(iced::Alignment::Center),
+                );
+                col = col.push(table.heading(&u.profile));
+                if u.current_streak(Period::Day) > 0 {
+                    let mut streaks = iced::widget::row![].spacing(20);
+                    for period in [Period::Day, Period::Week] {
+                        streaks = streaks.push(table.streak(
+                            period,
+                            u.current_streak(period),
+                            u.longest_streak(period),
+                        ));
+                    }
+                    if let Some((from, _, days)) = u.gaps(1).into_iter().max_by_key(|g| g.2) {
+                        streaks = streaks.push(table.longest_break(&from, days));
+                    }
+                    col = col.push(streaks);
+                }
+                if u.profile_revisions.len() > 1 {
+                    let choices: Vec<RevisionChoice> = u
+                        .profile_revisions
+                        .iter()
+                        .enumerate()
+                        .map(|(index, profile)| RevisionChoice {
+                            index,
+                            height_cm: profile.height_cm,
+                            birth_date: profile.birth_date_dmy.to_string(),
+                        })
+                        .collect();
+                    let selected = choices.get(u.selected_revision).cloned();
+                    let user = u.index;
+                    col = col.push(
+                        iced::widget::row![
+                            text("Profile revision"),
+                            pick_list(choices, selected, move |choice: RevisionChoice| {
+                                Message::SelectProfileRevision(user, choice.index)
+                            }),
+                        ]
+                        .spacing(10)
+                        .align_y(iced::Alignment::Center),
+                    );
+                }
+                let test_readings = u.likely_test_readings().len();
+                if test_readings > 0 {
+                    col = col.push(
+                        iced::widget::row![
+                            text(format!(
+                                "The first {} reading(s) look like test weigh-ins",
+                                test_readings
+                            )),
+                            button("Exclude").on_press(Message::ExcludeTestReadings(u.index)),
+                        ]
+                        .spacing(10)
+                        .align_y(iced::Alignment::Center),
+                    );
+                }
+                let mut changes = iced::widget::column![].spacing(2);
+                for change in Metric::ALL.iter().filter_map(|m| u.overall_change(*m)) {
+                    changes = changes.push(table.change(&change));
+                }
+                col = col.push(changes);
+                col = col.push(
+                    iced::widget::row![
+                        pick_list(
+                            Metric::ALL,
+                            Some(self.chart_metric),
+                            Message::SetChartMetric
+                        ),
+                        canvas(self.trend_chart(u))
+                            .width(Length::Fill)
+                            .height(Length::Fixed(120.0)),
+                        button("Export SVG").on_press(Message::ExportChartSvg),
+                    ]
+                    .spacing(10),
+                );
+                col = col.push(self.session_view(u));
+                let mut exports = iced::widget::row![].spacing(10);
+                for preset in ExportPreset::ALL {
+                    exports = exports.push(
+                        button(text(format!("Export {}", preset)))
+                            .on_press(Message::ExportUser(preset)),
+                    );
+                }
+                exports = exports.push(
+                    iced::widget::row![
+                        text("Export units"),
+                        pick_list(
+                            Units::ALL,
+                            Some(self.settings.export_units),
+                            Message::SetExportUnits
+                        ),
+                    ]
+                    .spacing(6)
+                    .align_y(iced::Alignment::Center),
+                );
+                exports =
+                    exports.push(button("Export milestones").on_press(Message::ExportMilestones));
+                col = col.push(exports);
+                let wrong_user = u
+                    .measurements
+                    .iter()
+                    .filter(|m| m.possibly_wrong_user)
+                    .count();
+                if wrong_user > 0 {
+                    col = col.push(text(format!(
+                        "{} measurement(s) echo a gender or height that differs from this profile, possibly the wrong user",
+                        wrong_user
+                    )));
+                }
+                let bad_checksum = u.measurements.iter().filter(|m| !m.checksum_ok).count();
+                if bad_checksum > 0 {
+                    col = col.push(text(format!(
+                        "{} measurement(s) fail their checksum and may be corrupt; they are tagged \"corrupt?\"",
+                        bad_checksum
+                    )).style(text::danger));
+                }
+                let clock_unset = u.measurements.iter().filter(|m| m.clock_unset()).count();
+                if clock_unset > 0 {
+                    col = col.push(text(format!(
+                        "{} measurement(s) were taken before the scale's clock was set and have no date",
+                        clock_unset
+                    )));
+                }
+                if let Some(editor) = &self.editor
+                    && editor.user == u.index
+                {
+                    col = col.push(Self::editor_view(editor, u));
+                    if let Some(raw_row) = u
+                        .measurements
+                        .iter()
+                        .find(|m| m.fingerprint() == editor.fingerprint)
+                        .and_then(|m| m.raw_row.as_deref())
+                    {
+                        col = col.push(text(raw_row).size(12));
+                    }
+                }
+                if u.data_file_empty {
+                    col = col.push(text("No measurements recorded for this user yet"));
+                } else if u.measurements.is_empty() {
+                    col = col.push(text("None of this user's measurements could be parsed"));
+                } else {
+                    col = col.push(table.consistency(&u.consistency()));
+                    if let Some(std_dev) = u.typical_fluctuation() {
+                        col = col.push(table.fluctuation(std_dev));
+                    }
+                    if let Some(latest) = u.charted(self.settings.view_filter()).last()
+                        && let (Some(dci), Some(tdee)) =
+                            (latest.daily_calorie_intake_kcal, derived::tdee_kcal(latest))
+                    {
+                        col = col.push(table.calorie_comparison(dci, tdee));
+                    }
+                    // The older rows stay hidden, at the top or, newest first, at the bottom.
+                    let hidden = self.filtered.len().saturating_sub(self.visible_count);
+                    let load_more = (hidden > 0).then(|| {
+                        button(text(format!(
+                            "Load more ({} older measurement(s) hidden)",
+                            hidden
+                        )))
+                        .on_press(Message::LoadMore)
+                    });
+                    let tags = u.auto_tags();
+                    if self.settings.newest_first {
+                        let shown = &self.filtered[..self.filtered.len() - hidden];
+                        col = col.push(table.body(u.index, &u.measurements, shown, &tags));
+                        col = col.push_maybe(load_more);
+                    } else {
+                        let shown = &self.filtered[hidden..];
+                        col = col.push_maybe(load_more);
+                        col = col.push(table.body(u.index, &u.measurements, shown, &tags));
+                    }
+                }
+            }
         }
 
         col
     }
 
+    /// Handles `message`, then schedules an auto-save if it changed settings or edits.
     fn update(&mut self, message: Message) -> Task<Message> {
-        match message {
-            Message::PickFileOrFolder => Task::perform(pick_folder(), Message::PathPicked),
+        let generation = self.save_generation;
+        let task = self.handle(message);
+        if self.save_generation == generation {
+            return task;
+        }
+        let (timer, handle) = delayed(AUTOSAVE_DEBOUNCE, Message::AutoSave(self.save_generation));
+        self.save_timer = Some(handle);
+        Task::batch([task, timer])
+    }
 
-            Message::PathPicked(path_buff) => {
-                match path_buff {
-                    Some(file) => {
-                        let parser = TanitaParser { root_dir: file };
-                        let raw = parser.get_raw_users_records();
-                        let mut ui_ready_measurments: Vec<UserMeasurements> =
-                            Vec::with_capacity(raw.len());
+    fn handle(&mut self, message: Message) -> Task<Message> {
+        match message {
+            Message::PickFileOrFolder => Task::perform(pick_folder(), Message::PathPicked),
 
-                        for e in raw {
-                            ui_ready_measurments.push(UserMeasurements::from_raw(e));
-                        }
-                        self.measurements = ui_ready_measurments;
+            Message::ScanDirectory => {
+                Task::perform(scan_for_tanita_folders(), Message::FoldersScanned)
+            }
+
+            Message::CardDetected(folder) => {
+                self.detected_card = Some(folder);
+                Task::none()
+            }
+
+            Message::DismissCard => {
+                self.detected_card = None;
+                Task::none()
+            }
+
+            Message::FoldersScanned(None) => Task::none(),
+
+            Message::FoldersScanned(Some(folders)) => {
+                self.load_error = folders
+                    .is_empty()
+                    .then(|| "No folder with DATA and SYSTEM inside it was found".to_string());
+                self.scanned_folders = folders;
+                Task::none()
+            }
+
+            Message::PathPicked(path_buff) => match path_buff {
+                Some(file) => {
+                    self.detected_card = None;
+                    if let Err(err) = check_picked_path(&file) {
+                        self.load_error = Some(err);
+                        return Task::none();
                     }
-                    None => {
-                        println!("path was not picked, how did u ended up here?");
+                    self.load_error = None;
+                    if let Some(job) = self.parsing.take() {
+                        job.cancel();
+                    }
+                    let mut parser = TanitaParser::new(file);
+                    if let Err(err) = parser.check_root() {
+                        self.load_error = Some(err.to_string());
+                        return Task::none();
+                    }
+                    self.notice = parser.picked_subfolder.as_ref().map(|picked| {
+                        format!(
+                            "{} is a DATA/SYSTEM folder, using {} as the root",
+                            picked.display(),
+                            parser.root_dir.display()
+                        )
+                    });
+                    parser.config.keep_raw = self.settings.keep_raw_rows;
+                    let cancel = parser.cancel.clone();
+                    let (task, handle) = Task::run(parse_with_progress(parser), |m| m).abortable();
+                    self.parsing = Some(ParseJob {
+                        cancel,
+                        handle,
+                        progress: None,
+                    });
+                    task
+                }
+                None => Task::none(),
+            },
+
+            // The job was already dropped by whoever cancelled it, and the previous
+            // dataset stays on screen.
+            Message::ParseCancelled => Task::none(),
+
+            Message::ParseProgressed(progress) => {
+                if let Some(job) = &mut self.parsing {
+                    job.progress = Some(progress);
+                }
+                Task::none()
+            }
+
+            Message::Parsed(file, raw) => {
+                // A cancelled job may still deliver its result; the previous dataset stays.
+                if self.parsing.take().is_none() {
+                    return Task::none();
+                }
+                let mut raw = match raw {
+                    Ok(raw) => raw,
+                    Err(err) => {
+                        self.load_error = Some(err);
+                        return Task::none();
+                    }
+                };
+                self.unpaired = raw.unpaired().map(|unpaired| unpaired.to_string());
+                let mut ui_ready_measurments: Vec<UserMeasurements> =
+                    Vec::with_capacity(raw.users.len());
+
+                for e in raw.users {
+                    let index = e.index;
+                    match UserMeasurements::from_raw(e) {
+                        Some(user) => ui_ready_measurments.push(user),
+                        None => raw.warnings.push(DatasetWarning {
+                            index,
+                            line: 0,
+                            kind: WarningKind::UnreadableProfile,
+                        }),
+                    }
+                }
+                // Edits still pending belong to the previous folder.
+                self.flush_saves();
+                self.edits = Edits::load(&file).unwrap_or_else(|err| {
+                    self.notice = Some(err);
+                    Edits::default()
+                });
+                self.edits.apply_all(&mut ui_ready_measurments);
+                self.skipped_files = raw.skipped_files;
+                self.index_collisions = raw.index_collisions;
+                self.dataset_warnings = raw.warnings;
+                self.show_dataset_warnings = false;
+                self.show_dataset(ui_ready_measurments, Some(file));
+                Task::none()
+            }
+
+            Message::OpenDump => Task::perform(read_dump(), Message::DumpRead),
+
+            Message::DumpRead(None) => Task::none(),
+
+            Message::DumpRead(Some(contents)) => {
+                let users = contents.and_then(|json| {
+                    serde_json::from_str::<Vec<UserMeasurements>>(&json)
+                        .map_err(|err| format!("Not a saved dump: {}", err))
+                });
+                match users {
+                    Ok(users) => {
+                        if let Some(job) = self.parsing.take() {
+                            job.cancel();
+                        }
+                        // Edits are already applied in the dump and there is
+                        // no folder to keep a sidecar in.
+                        self.flush_saves();
+                        self.edits = Edits::default();
+                        self.load_error = None;
+                        self.unpaired = None;
+                        self.skipped_files.clear();
+                        self.index_collisions.clear();
+                        self.dataset_warnings.clear();
+                        self.show_dataset(users, None);
                     }
+                    Err(err) => self.load_error = Some(err),
+                }
+                Task::none()
+            }
+
+            Message::CancelParse => {
+                if let Some(job) = self.parsing.take() {
+                    job.cancel();
                 }
                 Task::none()
             }
 
             Message::TabSelected(i) => {
                 self.selected_tab = i;
+                self.visible_count = self.settings.table_page_size;
+                self.session_from = None;
+                self.session_to = None;
+                self.refresh_filtered();
+                Task::none()
+            }
+
+            Message::SetUserSort(user_sort) => {
+                self.settings.user_sort = user_sort;
+                self.settings_changed();
+                Task::none()
+            }
+
+            Message::SetExportUnits(units) => {
+                self.settings.export_units = units;
+                self.settings_changed();
+                Task::none()
+            }
+
+            Message::SetNumberFormat(number_format) => {
+                self.settings.number_format = number_format;
+                self.settings_changed();
+                Task::none()
+            }
+
+            Message::ToggleProfileColumns(show) => {
+                self.settings.show_profile_columns = show;
+                self.settings_changed();
+                Task::none()
+            }
+
+            Message::ToggleCompact(compact) => {
+                self.settings.compact_table = compact;
+                self.settings_changed();
+                Task::none()
+            }
+
+            Message::ToggleMuscleOnly(muscle_only) => {
+                self.settings.muscle_only = muscle_only;
+                self.settings_changed();
+                self.refresh_filtered();
+                self.refresh_sparklines();
+                Task::none()
+            }
+
+            Message::ToggleFullFeatureEra(era_only) => {
+                self.settings.full_feature_era_only = era_only;
+                self.settings_changed();
+                self.refresh_filtered();
+                self.refresh_sparklines();
+                Task::none()
+            }
+
+            Message::ToggleFatDistribution(show) => {
+                self.settings.show_fat_distribution = show;
+                self.settings_changed();
+                Task::none()
+            }
+
+            Message::ToggleSortOrder(newest_first) => {
+                self.settings.newest_first = newest_first;
+                self.settings_changed();
+                self.refresh_filtered();
+                Task::none()
+            }
+
+            Message::ToggleUnits(imperial) => {
+                self.settings.display_units = if imperial {
+                    Units::Imperial
+                } else {
+                    Units::Metric
+                };
+                self.settings_changed();
+                Task::none()
+            }
+
+            Message::ToggleHighContrast(high_contrast) => {
+                self.settings.high_contrast = high_contrast;
+                self.settings_changed();
+                Task::none()
+            }
+
+            Message::SetFontScale(font_scale) => {
+                self.settings.font_scale = font_scale;
+                self.settings_changed();
+                Task::none()
+            }
+
+            Message::AutoSave(generation) => {
+                if generation == self.save_generation {
+                    self.flush_saves();
+                }
+                Task::none()
+            }
+
+            Message::SetChartMetric(metric) => {
+                self.chart_metric = metric;
+                Task::none()
+            }
+
+            Message::SearchChanged(search) => {
+                self.search = search;
+                self.search_generation += 1;
+                let (timer, handle) = delayed(
+                    SEARCH_DEBOUNCE,
+                    Message::ApplySearch(self.search_generation),
+                );
+                self.search_timer = Some(handle);
+                timer
+            }
+
+            Message::ApplySearch(generation) => {
+                if generation == self.search_generation && self.applied_search != self.search {
+                    self.applied_search = self.search.clone();
+                    self.refresh_filtered();
+                }
+                Task::none()
+            }
+
+            Message::ToggleValueSource(recomputed) => {
+                self.settings.show_recomputed = recomputed;
+                self.settings_changed();
+                Task::none()
+            }
+
+            Message::ToggleKeepRawRows(keep) => {
+                self.settings.keep_raw_rows = keep;
+                self.settings_changed();
+                Task::none()
+            }
+
+            Message::EditMeasurement(user, fingerprint) => {
+                self.editor = Some(MeasurementEditor {
+                    user,
+                    fingerprint,
+                    field: Field::WeightKg,
+                    value: String::new(),
+                });
+                Task::none()
+            }
+
+            Message::EditorFieldSelected(field) => {
+                if let Some(editor) = &mut self.editor {
+                    editor.field = field;
+                }
+                Task::none()
+            }
+
+            Message::EditorValueChanged(value) => {
+                if let Some(editor) = &mut self.editor {
+                    editor.value = value;
+                }
+                Task::none()
+            }
+
+            Message::OverrideMeasurement(user, fingerprint, field, value) => {
+                self.edits
+                    .set_override(&mut self.measurements, user, fingerprint, field, value);
+                self.edits_changed();
+                self.refresh_filtered();
+                Task::none()
+            }
+
+            Message::ExcludeMeasurement(user, fingerprint, excluded) => {
+                self.edits
+                    .set_excluded(&mut self.measurements, user, fingerprint, excluded);
+                self.edits_changed();
+                Task::none()
+            }
+
+            Message::ToggleMilestone(user, fingerprint) => {
+                let milestone = self
+                    .measurements
+                    .iter()
+                    .find(|u| u.index == user)
+                    .and_then(|u| {
+                        u.measurements
+                            .iter()
+                            .find(|m| m.fingerprint() == fingerprint)
+                    })
+                    .is_some_and(|m| m.milestone);
+                self.edits
+                    .set_milestone(&mut self.measurements, user, fingerprint, !milestone);
+                self.edits_changed();
+                Task::none()
+            }
+
+            Message::ExcludeTestReadings(user) => {
+                let fingerprints: Vec<u64> = self
+                    .measurements
+                    .iter()
+                    .find(|u| u.index == user)
+                    .map(|u| {
+                        u.likely_test_readings()
+                            .into_iter()
+                            .map(|i| u.measurements[i].fingerprint())
+                            .collect()
+                    })
+                    .unwrap_or_default();
+                for fingerprint in fingerprints {
+                    self.edits
+                        .set_excluded(&mut self.measurements, user, fingerprint, true);
+                }
+                self.edits_changed();
+                Task::none()
+            }
+
+            Message::CloseEditor => {
+                self.editor = None;
+                Task::none()
+            }
+
+            Message::CopyToClipboard(contents) => iced::clipboard::write(contents),
+
+            Message::SelectProfileRevision(user, revision) => {
+                if let Some(u) = self.measurements.iter_mut().find(|u| u.index == user) {
+                    u.select_profile_revision(revision);
+                }
+                Task::none()
+            }
+
+            Message::SetPrimaryMetric(user, metric) => {
+                self.settings.primary_metrics.insert(user, metric);
+                self.settings_changed();
+                Task::none()
+            }
+
+            Message::LoadMore => {
+                self.visible_count += self.settings.table_page_size;
+                Task::none()
+            }
+
+            Message::MergeUsers(group) => {
+                self.merge_users(&group);
+                Task::none()
+            }
+
+            Message::SetMergeTolerance(tolerance) => {
+                self.settings.merge_tolerance = tolerance;
+                self.settings_changed();
+                Task::none()
+            }
+
+            Message::SaveUnknownTagReport => {
+                let report = unknown_tag_report(&self.measurements);
+                match serde_json::to_string_pretty(&report) {
+                    Ok(json) => Task::perform(
+                        export::save_to_file("unknown_tags.json".to_string(), json),
+                        Message::FileSaved,
+                    ),
+                    Err(err) => {
+                        self.notice = Some(format!("Unknown tags report error: {}", err));
+                        Task::none()
+                    }
+                }
+            }
+
+            Message::ToggleDatasetWarnings => {
+                self.show_dataset_warnings = !self.show_dataset_warnings;
+                Task::none()
+            }
+
+            Message::SaveParseWarningReport => Task::perform(
+                export::save_to_file(
+                    "parse_warnings.txt".to_string(),
+                    parse_warning_report(&self.measurements),
+                ),
+                Message::FileSaved,
+            ),
+
+            Message::SetSessionFrom(date_time) => {
+                self.session_from = Some(date_time);
+                Task::none()
+            }
+
+            Message::SetSessionTo(date_time) => {
+                self.session_to = Some(date_time);
+                Task::none()
+            }
+
+            Message::ExportSession(format) => {
+                let Some(u) = self.selected_user() else {
+                    return Task::none();
+                };
+                let (Some(from), Some(to)) = self.session_bounds(u) else {
+                    return Task::none();
+                };
+                match u.session_comparison(&from, &to) {
+                    Some(comparison) => Task::perform(
+                        export::save_to_file(
+                            format!("session_user{}.{}", u.index + 1, format.file_extension()),
+                            comparison.render(format),
+                        ),
+                        Message::FileSaved,
+                    ),
+                    None => {
+                        self.notice = Some(format!("Nothing to compare for user {}", u.index + 1));
+                        Task::none()
+                    }
+                }
+            }
+
+            Message::ExportUser(preset) => match self.selected_user() {
+                Some(u) => Task::perform(
+                    export::save_to_file(
+                        preset.file_name(u.index),
+                        u.export_csv(preset, self.settings.export_units),
+                    ),
+                    Message::FileSaved,
+                ),
+                None => Task::none(),
+            },
+
+            Message::ExportMilestones => match self.selected_user() {
+                Some(u) => Task::perform(
+                    export::save_to_file(
+                        format!("user{}_milestones.csv", u.index + 1),
+                        u.export_milestones(self.settings.export_units),
+                    ),
+                    Message::FileSaved,
+                ),
+                None => Task::none(),
+            },
+
+            Message::ExportChartSvg => match self.selected_user() {
+                Some(u) => {
+                    let chart = self.trend_chart(u);
+                    Task::perform(
+                        export::save_to_file(
+                            format!("user{}_chart.svg", u.index + 1),
+                            chart.to_svg(),
+                        ),
+                        Message::FileSaved,
+                    )
+                }
+                None => Task::none(),
+            },
+
+            Message::ExportDebugDump => match serde_json::to_string_pretty(&self.measurements) {
+                Ok(json) => Task::perform(
+                    export::save_to_file("tanita_dump.json".to_string(), json),
+                    Message::FileSaved,
+                ),
+                Err(err) => {
+                    self.notice = Some(format!("Debug dump error: {}", err));
+                    Task::none()
+                }
+            },
+
+            Message::ExportHouseholdTimeline => Task::perform(
+                export::save_to_file(
+                    "household_timeline.csv".to_string(),
+                    export::export_household_timeline_since(
+                        &self.measurements,
+                        None,
+                        self.settings.export_units,
+                    ),
+                ),
+                Message::FileSaved,
+            ),
+
+            Message::ExportSinceLastSync => {
+                let after = self.settings.last_sync.clone();
+                let contents = export::export_household_timeline_since(
+                    &self.measurements,
+                    after.as_ref(),
+                    self.settings.export_units,
+                );
+                let latest = self
+                    .measurements
+                    .iter()
+                    .filter_map(|u| u.latest_measurement())
+                    .max()
+                    .cloned()
+                    .max(after);
+                Task::perform(
+                    export::save_to_file("household_since_last_sync.csv".to_string(), contents),
+                    move |result| Message::SyncExported(result, latest.clone()),
+                )
+            }
+
+            Message::SyncExported(result, latest) => {
+                if let Ok(Some(_)) = &result {
+                    self.settings.last_sync = latest;
+                    self.settings_changed();
+                }
+                self.handle(Message::FileSaved(result))
+            }
+
+            Message::FileSaved(result) => {
+                match result {
+                    Ok(Some(path)) => self.notice = Some(format!("Saved {}", path.display())),
+                    Ok(None) => {}
+                    Err(err) => self.notice = Some(format!("Save error: {}", err)),
+                }
                 Task::none()
             }
         }
     }
 
-    fn theme(_state: &Application) -> iced::Theme {
-        Theme::Ferra
+    /// Dated, not excluded timestamps of `u`, oldest first.
+    fn session_choices(u: &UserMeasurements) -> Vec<DateTime> {
+        let mut choices: Vec<DateTime> = u
+            .measurements
+            .iter()
+            .filter(|m| !m.excluded)
+            .filter_map(|m| m.date_time.clone())
+            .collect();
+        choices.sort();
+        choices.dedup();
+        choices
+    }
+
+    /// The chosen bounds, defaulting to the last two measurements.
+    fn session_bounds(&self, u: &UserMeasurements) -> (Option<DateTime>, Option<DateTime>) {
+        let choices = Self::session_choices(u);
+        let from = self.session_from.clone().or_else(|| {
+            choices
+                .len()
+                .checked_sub(2)
+                .and_then(|i| choices.get(i).cloned())
+        });
+        let to = self.session_to.clone().or_else(|| choices.last().cloned());
+        (from, to)
+    }
+
+    fn session_view<'a>(&self, u: &UserMeasurements) -> Row<'a, Message> {
+        let choices = Self::session_choices(u);
+        let (from, to) = self.session_bounds(u);
+        let can_export = from.is_some() && to.is_some();
+        iced::widget::row![
+            text("Session from"),
+            pick_list(choices.clone(), from, Message::SetSessionFrom),
+            text("to"),
+            pick_list(choices, to, Message::SetSessionTo),
+            button("Export CSV")
+                .on_press_maybe(can_export.then_some(Message::ExportSession(SessionFormat::Csv))),
+            button("Export text")
+                .on_press_maybe(can_export.then_some(Message::ExportSession(SessionFormat::Text))),
+        ]
+        .spacing(10)
+        .align_y(iced::Alignment::Center)
+    }
+
+    /// Moves the measurements of `group[1..]` into `group[0]` and drops those
+    /// users, keeping one copy of readings within `settings.merge_tolerance`
+    /// of each other. Only the loaded dataset changes; the device files stay as they are.
+    fn merge_users(&mut self, group: &[usize]) {
+        let Some((&keep, rest)) = group.split_first() else {
+            return;
+        };
+        let mut per_user: BTreeMap<usize, UserMeasurements> =
+            into_per_user(mem::take(&mut self.measurements))
+                .into_iter()
+                .collect();
+        let merged: Vec<UserMeasurements> =
+            rest.iter().filter_map(|i| per_user.remove(i)).collect();
+        if let Some(target) = per_user.get_mut(&keep) {
+            for u in merged {
+                target.absorb(u);
+            }
+            let removed = target.dedupe_near_duplicates(&self.settings.merge_tolerance);
+            if removed > 0 {
+                self.notice = Some(format!(
+                    "Merge dropped {} near-duplicate measurement(s)",
+                    removed
+                ));
+            }
+        }
+        self.measurements = from_per_user(per_user);
+        if rest.contains(&self.selected_tab) {
+            self.selected_tab = keep;
+        }
+        self.editor = None;
+        self.refresh_filtered();
+        self.refresh_sparklines();
+    }
+
+    /// `chart_metric` of `u` over time, over its healthy band where there is one.
+    fn trend_chart(&self, u: &UserMeasurements) -> TrendChart {
+        let filter = self.settings.view_filter();
+        let milestones: Vec<(DateTime, f32)> = u
+            .charted(filter)
+            .into_iter()
+            .filter(|m| m.milestone)
+            .filter_map(|m| Some((m.date_time.clone()?, self.chart_metric.value(m)?)))
+            .collect();
+        TrendChart::new(self.chart_metric, &u.series(self.chart_metric, filter))
+            .with_units(self.settings.display_units)
+            .with_band(&u.healthy_band(self.chart_metric, filter))
+            .with_markers(&milestones)
+            .with_gaps(&u.gaps_in_view(filter, chart::MAX_TREND_GAP_DAYS as u32))
+    }
+
+    /// The user whose tab is selected. Falls back to the first user if the
+    /// stored index is not in the dataset, so a stale index never blanks the view.
+    fn selected_user(&self) -> Option<&UserMeasurements> {
+        self.measurements
+            .iter()
+            .find(|u| u.index == self.selected_tab)
+            .or_else(|| self.measurements.first())
+    }
+
+    /// Rows of the selected user left after the view filter and the
+    /// applied search (a case-insensitive match on the date and time text),
+    /// oldest first, or newest first if so set. Readings with the same
+    /// timestamp stay in file order either way.
+    fn refresh_filtered(&mut self) {
+        let Some(u) = self.selected_user() else {
+            self.filtered.clear();
+            return;
+        };
+        let query = self.applied_search.trim().to_lowercase();
+        let mut filtered: Vec<usize> = u
+            .in_view(self.settings.view_filter())
+            .filter(|(_, m)| {
+                query.is_empty()
+                    || m.date_time
+                        .as_ref()
+                        .is_some_and(|dt| dt.to_string().to_lowercase().contains(&query))
+            })
+            .map(|(i, _)| i)
+            .collect();
+        let newest_first = self.settings.newest_first;
+        filtered.sort_by(|&a, &b| {
+            let (a, b) = (&u.measurements[a], &u.measurements[b]);
+            if newest_first {
+                b.date_time
+                    .cmp(&a.date_time)
+                    .then(a.source_line.cmp(&b.source_line))
+            } else {
+                a.cmp(b)
+            }
+        });
+        self.filtered = filtered;
+    }
+
+    /// Replaces the loaded users and resets what referred to the previous ones.
+    fn show_dataset(&mut self, users: Vec<UserMeasurements>, root: Option<PathBuf>) {
+        self.measurements = users;
+        self.editor = None;
+        self.session_from = None;
+        self.session_to = None;
+        self.visible_count = self.settings.table_page_size;
+        self.root_dir = root;
+        // A smaller dataset may not have the previously selected user.
+        if let Some(u) = self.selected_user() {
+            self.selected_tab = u.index;
+        }
+        self.refresh_filtered();
+        self.refresh_sparklines();
+    }
+
+    /// Rebuilds every tab's sparkline from the weights that pass the view filter.
+    fn refresh_sparklines(&mut self) {
+        let filter = self.settings.view_filter();
+        self.sparklines = self
+            .measurements
+            .iter()
+            .map(|u| (u.index, Sparkline::new(&u.weights_chronological(filter))))
+            .collect();
+    }
+
+    fn settings_changed(&mut self) {
+        self.unsaved_settings = true;
+        self.save_generation += 1;
+    }
+
+    fn edits_changed(&mut self) {
+        self.unsaved_edits = true;
+        self.save_generation += 1;
+        self.refresh_sparklines();
+    }
+
+    /// Writes whatever is pending. Failures keep it pending and are shown
+    /// until a later save succeeds.
+    fn flush_saves(&mut self) {
+        let mut errors = Vec::new();
+        if self.unsaved_settings {
+            match self.settings.save() {
+                Ok(()) => self.unsaved_settings = false,
+                Err(err) => errors.push(format!("settings: {}", err)),
+            }
+        }
+        if self.unsaved_edits {
+            match &self.root_dir {
+                Some(root) => match self.edits.save(root) {
+                    Ok(()) => self.unsaved_edits = false,
+                    Err(err) => errors.push(format!("edits: {}", err)),
+                },
+                None => self.unsaved_edits = false,
+            }
+        }
+        self.save_error = (!errors.is_empty()).then(|| errors.join("; "));
+    }
+
+    fn editor_view<'a>(
+        editor: &'a MeasurementEditor,
+        user: &'a UserMeasurements,
+    ) -> Row<'a, Message> {
+        let measurement = user
+            .measurements
+            .iter()
+            .find(|m| m.fingerprint() == editor.fingerprint);
+        let excluded = measurement.is_some_and(|m| m.excluded);
+        let (u, fp, field) = (editor.user, editor.fingerprint, editor.field);
+        let title = match measurement.and_then(|m| m.date_time.as_ref()) {
+            Some(date_time) => format!("Edit {}", date_time),
+            None => "Edit undated measurement".to_string(),
+        };
+
+        iced::widget::row![
+            text(title),
+            pick_list(Field::ALL, Some(field), Message::EditorFieldSelected),
+            text_input("new value", &editor.value)
+                .on_input(Message::EditorValueChanged)
+                .width(Length::Fixed(100.0)),
+            button("Apply").on_press_maybe(
+                editor
+                    .value
+                    .trim()
+                    .parse::<f32>()
+                    .ok()
+                    .map(|v| Message::OverrideMeasurement(u, fp, field, Some(v)))
+            ),
+            button("Reset").on_press(Message::OverrideMeasurement(u, fp, field, None)),
+            checkbox("Excluded", excluded)
+                .on_toggle(move |b| Message::ExcludeMeasurement(u, fp, b)),
+            button("Copy").on_press_maybe(measurement.map(|m| {
+                Message::CopyToClipboard(
+                    m.to_map(Units::Metric)
+                        .into_iter()
+                        .map(|(label, value)| format!("{}: {}\n", label, value))
+                        .collect(),
+                )
+            })),
+            button("Close").on_press(Message::CloseEditor),
+        ]
+        .spacing(10)
+        .align_y(iced::Alignment::Center)
     }
 
-    fn title(_state: &Application) -> String {
-        String::from("Here is the title of my app (todo: find a nice name)")
+    fn theme(state: &Application) -> iced::Theme {
+        if state.settings.high_contrast {
+            HIGH_CONTRAST_THEME.clone()
+        } else {
+            Theme::Ferra
+        }
+    }
+
+    /// Watches `settings.card_mount_path` when one is configured.
+    fn subscription(&self) -> Subscription<Message> {
+        match &self.settings.card_mount_path {
+            Some(mount) => Subscription::run_with_id(
+                mount.clone(),
+                watch_card(mount.clone()).map(Message::CardDetected),
+            ),
+            None => Subscription::none(),
+        }
+    }
+
+    fn title(state: &Application) -> String {
+        match &state.root_dir {
+            Some(root) => format!("{} — {}", APP_TITLE, root.display()),
+            None => APP_TITLE.to_string(),
+        }
+    }
+
+    /// What to pick on first start, with the card layout the parser expects.
+    fn empty_state<'a>() -> Column<'a, Message> {
+        iced::widget::column![
+            text("No measurements loaded yet").size(24),
+            text(
+                "Take the SD card out of the scale and put it in this computer. \
+                 On the card, open the TANITA folder and choose the GRAPHV1 folder inside it: \
+                 the one that holds DATA and SYSTEM."
+            ),
+            text(CARD_LAYOUT).font(iced::Font::MONOSPACE),
+            text(
+                "Picking DATA or SYSTEM itself also works. With several cards copied \
+                 into one folder, use Scan directory to list them all."
+            ),
+        ]
+        .spacing(10)
+        .max_width(600)
+    }
+
+    /// Adds a computed column to every measurement table, after the device columns.
+    pub fn register_derived(&mut self, name: &str, f: DeriveFn) {
+        self.derived.push(DerivedColumn {
+            name: name.to_string(),
+            compute: f,
+        });
+    }
+
+    /// The app as `run` starts it: settings read from disk, the built-in
+    /// derived columns and those for `Settings::extra_columns`. Register more
+    /// with `register_derived` and start it with `run_prepared`.
+    pub fn new() -> Application {
+        let (settings, notice) = match Settings::load() {
+            Ok(settings) => (settings, None),
+            Err(err) => (Settings::default(), Some(err)),
+        };
+        let mut app = Application {
+            settings,
+            notice,
+            ..Application::default()
+        };
+        app.register_derived("Fat mass (kg)", Box::new(derived::fat_mass_kg));
+        app.register_derived("Lean mass (kg)", Box::new(derived::lean_mass_kg));
+        app.register_derived("Lean mass index", Box::new(derived::lean_mass_index));
+        app.register_derived("Fat mass index", Box::new(derived::fat_mass_index));
+        for key in app.settings.extra_columns.clone() {
+            app.register_derived(
+                &key.clone(),
+                Box::new(move |m: &Measurement| m.extra_f32(&key)),
+            );
+        }
+        app
     }
 
     pub fn run() -> iced::Result {
+        Self::run_prepared(Application::new())
+    }
+
+    /// Opens the window with `app`, e.g. one given extra columns with `register_derived`.
+    pub fn run_prepared(app: Application) -> iced::Result {
         iced::application(Self::title, Self::update, Self::view)
             .theme(Self::theme)
-            .run()
+            .subscription(Self::subscription)
+            .run_with(move || (app, Task::none()))
     }
 }
 
-struct TableBuilder {}
-impl TableBuilder {
-    fn text_w100<'a, T>(t: T) -> Text<'a>
-    where
-        T: text::IntoFragment<'a>,
-    {
-        text(t).width(Length::Fixed(100.0))
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use parser::TanitaParser;
+
+    const PROFILE_ROW: &str = r#"MO,"BC-601",DB,"14/06/1991",Bt,0,GE,1,Hm,180.0,AL,2,CS,38"#;
+
+    /// A user with one measurement per `(date, weight_kg)`, each at 08:00 and 20% fat.
+    pub(crate) fn user(index: usize, weights: &[(&str, f32)]) -> UserMeasurements {
+        let timed: Vec<(&str, &str, f32)> = weights
+            .iter()
+            .map(|(date, weight_kg)| (*date, "08:00:00", *weight_kg))
+            .collect();
+        user_at(index, &timed)
     }
 
-    fn text_w50<'a, T>(t: T) -> Text<'a>
-    where
-        T: text::IntoFragment<'a>,
-    {
-        text(t).width(Length::Fixed(50.0))
+    /// A user with one measurement per `(date, time, weight_kg)`, each at 20%
+    /// fat, numbered from line 1 in the given order.
+    pub(crate) fn user_at(index: usize, weights: &[(&str, &str, f32)]) -> UserMeasurements {
+        let config = parser::ParserConfig::default();
+        let data = weights
+            .iter()
+            .enumerate()
+            .map(|(line, (date, time, weight_kg))| {
+                let row = parser::tests::with_checksum(&format!(
+                    r#"MO,"BC-601",DT,"{}",Ti,"{}",GE,1,Hm,180.0,Wk,{},FW,20.0"#,
+                    date, time, weight_kg
+                ));
+                let mut raw = DataRaw::from_csv_row(&row, &config);
+                raw.source_line = line + 1;
+                raw
+            })
+            .collect();
+        UserMeasurements::from_raw(RawUserRecord {
+            index,
+            profiles: vec![ProfRaw::from_csv_row(PROFILE_ROW)],
+            data,
+            data_file_empty: weights.is_empty(),
+        })
+        .unwrap()
     }
 
-    fn option_into_string<T>(val: Option<T>) -> String
-    where
-        T: ToString,
-    {
-        match val {
-            Some(v) => v.to_string(),
-            None => "-".to_string(),
-        }
+    #[test]
+    fn per_user_split_rejoins_in_index_order() {
+        let users = vec![
+            user(0, &[("01/01/2020", 80.0)]),
+            user(1, &[("02/01/2020", 60.0)]),
+            user(2, &[]),
+        ];
+        let mut per_user = into_per_user(users);
+        assert_eq!(
+            per_user.iter().map(|(index, _)| *index).collect::<Vec<_>>(),
+            [0, 1, 2]
+        );
+
+        per_user.reverse();
+        let rejoined = from_per_user(per_user);
+        assert_eq!(
+            rejoined
+                .iter()
+                .map(UserMeasurements::index)
+                .collect::<Vec<_>>(),
+            [0, 1, 2]
+        );
+        assert_eq!(rejoined[1].measurements()[0].weight_kg, 60.0);
     }
 
-    fn heading(profile: &Profile) -> Column<'_, Message> {
-        let title = iced::widget::row![
-            Self::text_w100("Birht date"),
-            Self::text_w100("Gender"),
-            Self::text_w100("Height"),
-            Self::text_w100("Activity level"),
-            Self::text_w100("Body level"),
-        ]
-        .spacing(10);
-        let content = iced::widget::row![
-            Self::text_w100(profile.birth_date_dmy.to_srting()),
-            Self::text_w100(profile.gender.to_string()),
-            Self::text_w100(profile.height_cm.to_string()),
-            Self::text_w100(profile.activity_level_code.to_string()),
-            Self::text_w100(profile.body_type_code.to_string()),
-        ]
-        .spacing(10);
-
-        iced::widget::column![title, content]
-    }
-
-    fn body(measurements: &[Measurement]) -> Column<'_, Message> {
-        let title = iced::widget::row![
-            Self::text_w50("Date and time"),
-            Self::text_w50("Age"),
-            Self::text_w50("Activity level"),
-            Self::text_w50("Body level"),
-            Self::text_w50("Weight (kg)"),
-            Self::text_w50("BMI"),
-            Self::text_w50("Fat (%)"),
-            Self::text_w50("Fat (%) torso"),
-            Self::text_w50("Fat (%) r arm"),
-            Self::text_w50("Fat (%) l arm"),
-            Self::text_w50("Fat (%) r leg"),
-            Self::text_w50("Fat (%) l leg"),
-            Self::text_w50("Muscle (%)"),
-            Self::text_w50("Muscle (%) torso"),
-            Self::text_w50("Muscle (%) r arm"),
-            Self::text_w50("Muscle (%) l arm"),
-            Self::text_w50("Muscle (%) r leg"),
-            Self::text_w50("Muscle (%) l leg"),
-            Self::text_w50("Bones (kg)"),
-            Self::text_w50("Water (%)"),
-            Self::text_w50("Visceral fat raiting"),
-            Self::text_w50("Metabolic age"),
-            Self::text_w50("Daily calorie intake (kcal)"),
+    #[test]
+    fn into_parts_and_new_keep_everything() {
+        let original = user(3, &[("01/01/2020", 80.0), ("02/01/2020", 79.5)]);
+        let (index, profile, measurements) = original.into_parts();
+        let rebuilt = UserMeasurements::new(index, profile, measurements);
+        assert_eq!(rebuilt.index(), 3);
+        assert_eq!(rebuilt.profile().height_cm(), 180.0);
+        assert_eq!(rebuilt.measurements().len(), 2);
+        assert!(!rebuilt.data_file_empty);
+    }
+
+    #[test]
+    fn measurements_sort_by_time_then_source_line() {
+        let (_, _, mut measurements) = user(
+            0,
+            &[
+                ("02/01/2020", 80.0),
+                ("01/01/2020", 81.0),
+                ("02/01/2020", 79.0),
+            ],
+        )
+        .into_parts();
+        measurements.reverse();
+        measurements.sort();
+        let lines: Vec<usize> = measurements.iter().map(|m| m.source_line).collect();
+        assert_eq!(lines, [2, 1, 3]);
+        assert!(measurements[0] < measurements[1]);
+
+        // equal is the same key, whatever the values
+        let (_, _, other) = user(0, &[("02/01/2020", 70.0)]).into_parts();
+        assert_eq!(other[0], measurements[1]);
+        assert_ne!(other[0], measurements[2]);
+    }
+
+    #[test]
+    fn fingerprint_survives_a_reparse() {
+        let data = [
+            r#"MO,"BC-601",DT,"01/01/2020",Ti,"08:00:00",Wk,80.0,FW,20.0"#,
+            r#"MO,"BC-601",DT,"02/01/2020",Ti,"08:00:00",Wk,79.5,FW,20.0"#,
         ]
-        .spacing(1);
-
-        let mut col = iced::widget::column![];
-
-        for measurement in measurements {
-            let r = iced::widget::row![
-                Self::text_w50(measurement.date_time.to_string()),
-                Self::text_w50(measurement.age_years),
-                Self::text_w50(measurement.activity_level_code),
-                Self::text_w50(measurement.body_type_code),
-                Self::text_w50(measurement.weight_kg),
-                Self::text_w50(measurement.bmi),
-                Self::text_w50(measurement.fat_percent),
-                Self::text_w50(measurement.fat_trunk_pct),
-                Self::text_w50(measurement.fat_right_arm_pct),
-                Self::text_w50(measurement.fat_left_arm_pct),
-                Self::text_w50(measurement.fat_right_leg_pct),
-                Self::text_w50(measurement.fat_left_leg_pct),
-                Self::text_w50(Self::option_into_string(measurement.muscle_percent)),
-                Self::text_w50(Self::option_into_string(measurement.muscle_trunk_pct)),
-                Self::text_w50(Self::option_into_string(measurement.muscle_right_arm_pct)),
-                Self::text_w50(Self::option_into_string(measurement.muscle_left_arm_pct)),
-                Self::text_w50(Self::option_into_string(measurement.muscle_right_leg_pct)),
-                Self::text_w50(Self::option_into_string(measurement.muscle_left_leg_pct)),
-                Self::text_w50(Self::option_into_string(measurement.bone_kg)),
-                Self::text_w50(Self::option_into_string(measurement.water_percent)),
-                Self::text_w50(Self::option_into_string(measurement.visceral_fat_rating)),
-                Self::text_w50(Self::option_into_string(measurement.metabolic_age_years)),
-                Self::text_w50(Self::option_into_string(
-                    measurement.daily_calorie_intake_kcal
-                )),
-            ]
-            .spacing(1);
-            col = col.push(r);
-            col = col.push(horizontal_rule(1));
+        .map(parser::tests::with_checksum)
+        .join("\n");
+        let root = parser::tests::card("fingerprint", &[(1, PROFILE_ROW, &data)]);
+        let fingerprints = || -> Vec<u64> {
+            let dataset = TanitaParser::new(root.clone())
+                .get_raw_users_records()
+                .unwrap();
+            let user = UserMeasurements::from_raw(dataset.users.into_iter().next().unwrap());
+            user.unwrap()
+                .measurements()
+                .iter()
+                .map(Measurement::fingerprint)
+                .collect()
+        };
+        let first = fingerprints();
+        assert_eq!(fingerprints(), first);
+        assert_ne!(first[0], first[1]);
+        std::fs::remove_dir_all(root).unwrap();
+    }
+
+    #[cfg(feature = "gui")]
+    #[test]
+    fn fingerprint_survives_a_weight_override() {
+        let mut users = vec![user(0, &[("01/01/2020", 80.0), ("02/01/2020", 79.5)])];
+        let before = users[0].measurements()[0].fingerprint();
+        Edits::default().set_override(&mut users, 0, before, Field::WeightKg, Some(75.0));
+        let m = &users[0].measurements()[0];
+        assert_eq!(m.weight_kg, 75.0);
+        assert_eq!(m.fingerprint(), before);
+        assert_ne!(users[0].measurements()[1].fingerprint(), before);
+    }
+
+    #[test]
+    fn height_in_meters_or_centimeters_reads_as_centimeters() {
+        for height in ["1.75", "175"] {
+            let row = PROFILE_ROW.replace("180.0", height);
+            let profile = Profile::from_raw(ProfRaw::from_csv_row(&row)).unwrap();
+            assert_eq!(profile.height_cm(), 175.0);
         }
+    }
 
-        iced::widget::column![
-            title,
-            horizontal_rule(2),
-            scrollable(col).direction(scrollable::Direction::Vertical(
-                scrollable::Scrollbar::new()
-                    .width(14)
-                    .scroller_width(8)
-                    .spacing(4)
-            )) // .width(Length::Fill)
-               // .height(Length::Fill)
+    #[test]
+    fn map_labels_match_to_map() {
+        let user = user(0, &[("01/01/2020", 80.0)]);
+        for units in Units::ALL {
+            let labels: Vec<_> = user.measurements()[0]
+                .to_map(units)
+                .into_iter()
+                .map(|(label, _)| label)
+                .collect();
+            assert_eq!(Measurement::map_labels(units), labels);
+        }
+    }
+
+    #[test]
+    fn placeholder_date_keeps_the_measurement_undated() {
+        let user = user(0, &[("00/00/0000", 81.0), ("01/01/2020", 80.0)]);
+        let undated = &user.measurements()[0];
+        assert_eq!(undated.date_time, None);
+        assert_eq!(undated.weight_kg, 81.0);
+        assert!(user.measurements()[1].date_time().is_some());
+    }
+
+    #[cfg(feature = "gui")]
+    #[test]
+    fn smaller_dataset_moves_the_selection_to_an_existing_user() {
+        let dataset = |users: usize| {
+            (0..users)
+                .map(|index| user(index, &[("01/01/2020", 80.0)]))
+                .collect()
+        };
+        let mut app = Application::default();
+        app.show_dataset(dataset(5), None);
+        app.selected_tab = 4;
+        app.refresh_filtered();
+        assert_eq!(app.selected_user().map(UserMeasurements::index), Some(4));
+
+        app.show_dataset(dataset(2), None);
+        assert_eq!(app.selected_tab, 0);
+        assert_eq!(app.selected_user().map(UserMeasurements::index), Some(0));
+        assert_eq!(app.filtered, [0]);
+        assert_eq!(app.sparklines.keys().copied().collect::<Vec<_>>(), [0, 1]);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn parsed_tree_round_trips_through_json() {
+        let data = [
+            r#"MO,"BC-601",DT,"01/01/2020",Ti,"08:00:00",GE,1,Hm,180.0,Wk,80.0,FW,20.0,Fr,18.5,mW,60.1,Zx,3,CS,12"#,
+            r#"MO,"BC-601",DT,"00/00/0000",Ti,"08:00:00",GE,1,Hm,180.0,Wk,81.0,FW,20.5,CS,02"#,
         ]
+        .join("\n");
+        let root = parser::tests::card(
+            "serde",
+            &[
+                (1, PROFILE_ROW, &data),
+                (2, &PROFILE_ROW.replace("GE,1", "GE,7"), ""),
+            ],
+        );
+        let users: Vec<UserMeasurements> = TanitaParser::new(root.clone())
+            .get_raw_users_records()
+            .unwrap()
+            .users
+            .into_iter()
+            .filter_map(UserMeasurements::from_raw)
+            .collect();
+        std::fs::remove_dir_all(root).unwrap();
+
+        let json = serde_json::to_string_pretty(&users).unwrap();
+        let restored: Vec<UserMeasurements> = serde_json::from_str(&json).unwrap();
+        assert_eq!(restored.len(), users.len());
+        for (restored, user) in restored.iter().zip(&users) {
+            assert_eq!(restored.measurements(), user.measurements());
+            for (restored, m) in restored.measurements().iter().zip(user.measurements()) {
+                assert_eq!(restored.to_map(Units::Metric), m.to_map(Units::Metric));
+            }
+            assert_eq!(restored.profile().gender(), user.profile().gender());
+        }
+        assert_eq!(restored[1].profile().gender(), &Gender::Other(7));
+    }
+
+    #[cfg(feature = "gui")]
+    #[test]
+    fn concurrent_sleeps_wait_together() {
+        let start = std::time::Instant::now();
+        async_io::block_on(async {
+            iced::futures::future::join_all((0..100).map(|_| sleep(Duration::from_millis(20))))
+                .await
+        });
+        let elapsed = start.elapsed();
+        assert!(elapsed >= Duration::from_millis(20));
+        assert!(elapsed < Duration::from_secs(1), "{:?}", elapsed);
     }
 }
@@ -0,0 +1,593 @@
+use iced::{
+    Point, Rectangle, Renderer, Size, Theme, Vector, alignment, mouse,
+    widget::canvas::{self, Frame, Geometry, Path, Stroke},
+};
+
+use super::general_data_structs::DateTime;
+use super::metric::Metric;
+use super::units::Units;
+
+/// Upper bound on points drawn for a sparkline; longer histories are sampled down.
+const MAX_SPARKLINE_POINTS: usize = 64;
+/// Upper bound on points drawn for the trend chart.
+const MAX_TREND_POINTS: usize = 400;
+/// Neighbouring points further apart than this are not joined in the trend chart,
+/// so a long break in weighing doesn't look like a smooth change.
+pub const MAX_TREND_GAP_DAYS: f32 = 30.0;
+/// Room left of the plot for the y-axis labels.
+const AXIS_LABEL_WIDTH: f32 = 60.0;
+const AXIS_TEXT_SIZE: f32 = 11.0;
+/// Size of an exported SVG chart, in px; it scales freely once placed.
+const SVG_WIDTH: f32 = 800.0;
+const SVG_HEIGHT: f32 = 300.0;
+/// Radius of the dots marking milestones.
+const MARKER_RADIUS: f32 = 3.5;
+/// How close, in px, the cursor must be to a milestone line to show its label.
+const MARKER_HOVER_DISTANCE: f32 = 6.0;
+
+/// Reduces a time series to at most `max_points` with largest-triangle-three-buckets:
+/// the first and last points are kept and each bucket keeps the point that shapes
+/// the line most, so peaks and dips survive. Expects points sorted by time.
+pub fn downsample(points: &[(DateTime, f32)], max_points: usize) -> Vec<(DateTime, f32)> {
+    if points.len() <= max_points || max_points < 3 {
+        return points.to_vec();
+    }
+
+    let x = |i: usize| points[i].0.seconds_since_epoch() as f64;
+    let y = |i: usize| f64::from(points[i].1);
+    let len = points.len();
+    let bucket_size = (len - 2) as f64 / (max_points - 2) as f64;
+
+    let mut sampled = Vec::with_capacity(max_points);
+    sampled.push(points[0].clone());
+    let mut previous = 0;
+
+    for bucket in 0..max_points - 2 {
+        let start = (bucket as f64 * bucket_size) as usize + 1;
+        let end = ((bucket + 1) as f64 * bucket_size) as usize + 1;
+
+        // average of the next bucket (or the last point) is the third triangle corner
+        let next_start = end;
+        let next_end = (((bucket + 2) as f64 * bucket_size) as usize + 1).min(len);
+        let next_count = (next_end - next_start).max(1) as f64;
+        let (avg_x, avg_y) = if next_start < next_end {
+            (next_start..next_end).fold((0.0, 0.0), |(sx, sy), i| (sx + x(i), sy + y(i)))
+        } else {
+            (x(len - 1), y(len - 1))
+        };
+        let (avg_x, avg_y) = (avg_x / next_count, avg_y / next_count);
+
+        let area = |i: usize| {
+            ((x(previous) - avg_x) * (y(i) - y(previous))
+                - (x(previous) - x(i)) * (avg_y - y(previous)))
+            .abs()
+        };
+        let chosen = (start..end.min(len - 1))
+            .max_by(|a, b| area(*a).total_cmp(&area(*b)))
+            .unwrap_or(start);
+
+        sampled.push(points[chosen].clone());
+        previous = chosen;
+    }
+
+    sampled.push(points[len - 1].clone());
+    sampled
+}
+
+/// `(min_x, max_x, min_y, max_y)` of `points`.
+fn extent(points: &[(f32, f32)]) -> (f32, f32, f32, f32) {
+    points.iter().fold(
+        (
+            f32::INFINITY,
+            f32::NEG_INFINITY,
+            f32::INFINITY,
+            f32::NEG_INFINITY,
+        ),
+        |(lo_x, hi_x, lo_y, hi_y), (x, y)| (lo_x.min(*x), hi_x.max(*x), lo_y.min(*y), hi_y.max(*y)),
+    )
+}
+
+/// Maps data (x, y) within `extent` onto a plot of `size`, y growing upwards.
+fn scaler(
+    (min_x, max_x, min_y, max_y): (f32, f32, f32, f32),
+    size: Size,
+) -> impl Fn((f32, f32)) -> Point {
+    let range_x = if max_x > min_x { max_x - min_x } else { 1.0 };
+    let range_y = if max_y > min_y { max_y - min_y } else { 1.0 };
+    move |(x, y)| {
+        Point::new(
+            size.width * (x - min_x) / range_x,
+            size.height * (1.0 - (y - min_y) / range_y),
+        )
+    }
+}
+
+/// `points` (x, y) scaled from `extent` to fill `size`, split wherever consecutive
+/// points are more than `max_gap_x` apart.
+fn scaled_segments(
+    points: &[(f32, f32)],
+    extent: (f32, f32, f32, f32),
+    size: Size,
+    max_gap_x: f32,
+) -> Vec<Vec<Point>> {
+    if points.len() < 2 {
+        return Vec::new();
+    }
+
+    let point = scaler(extent, size);
+
+    let mut segments = vec![vec![point(points[0])]];
+    for pair in points.windows(2) {
+        if pair[1].0 - pair[0].0 > max_gap_x {
+            segments.push(Vec::new());
+        }
+        if let Some(segment) = segments.last_mut() {
+            segment.push(point(pair[1]));
+        }
+    }
+    segments
+}
+
+/// Polyline through `points`, see `scaled_segments`.
+fn line_path(
+    points: &[(f32, f32)],
+    extent: (f32, f32, f32, f32),
+    size: Size,
+    max_gap_x: f32,
+) -> Option<Path> {
+    let segments = scaled_segments(points, extent, size, max_gap_x);
+    if segments.is_empty() {
+        return None;
+    }
+
+    Some(Path::new(|builder| {
+        for segment in &segments {
+            builder.move_to(segment[0]);
+            for point in &segment[1..] {
+                builder.line_to(*point);
+            }
+        }
+    }))
+}
+
+/// Days since epoch and value of a downsampled series.
+fn to_days(points: &[(DateTime, f32)], max_points: usize) -> Vec<(f32, f32)> {
+    downsample(points, max_points)
+        .iter()
+        .map(|(date_time, v)| (date_time.seconds_since_epoch() as f32 / 86_400.0, *v))
+        .collect()
+}
+
+/// Tiny line of a single series (e.g. weight over time) without axes or labels.
+pub struct Sparkline {
+    /// Days since epoch and value, already downsampled.
+    points: Vec<(f32, f32)>,
+}
+
+impl Sparkline {
+    pub fn new(points: &[(DateTime, f32)]) -> Sparkline {
+        Sparkline {
+            points: to_days(points, MAX_SPARKLINE_POINTS),
+        }
+    }
+}
+
+/// One metric of one user over time, with its range and unit on the y-axis.
+/// The y-axis is scaled to the series and the healthy band, so each metric fills the plot.
+pub struct TrendChart {
+    metric: Metric,
+    /// Units of the y-axis labels; the points themselves stay in metric.
+    units: Units,
+    /// Days since epoch and value, already downsampled.
+    points: Vec<(f32, f32)>,
+    /// Days since epoch, low and high end of the healthy range, shaded behind the series.
+    band: Vec<(f32, f32, f32)>,
+    /// Days since epoch, value and label of the starred measurements, drawn as
+    /// a vertical line with a dot on the series. The label shows on hover.
+    markers: Vec<(f32, f32, String)>,
+    /// Days since epoch of the start and end of each break in weighing, shaded.
+    gaps: Vec<(f32, f32)>,
+}
+
+impl TrendChart {
+    /// `points` must be sorted by time; missing values are simply not in it.
+    pub fn new(metric: Metric, points: &[(DateTime, f32)]) -> TrendChart {
+        TrendChart {
+            metric,
+            units: Units::Metric,
+            points: to_days(points, MAX_TREND_POINTS),
+            band: Vec::new(),
+            markers: Vec::new(),
+            gaps: Vec::new(),
+        }
+    }
+
+    /// Labels the y-axis and markers in `units`.
+    pub fn with_units(mut self, units: Units) -> TrendChart {
+        self.units = units;
+        self
+    }
+
+    /// Shades `band` (time, low, high), sorted by time, behind the series.
+    pub fn with_band(mut self, band: &[(DateTime, f32, f32)]) -> TrendChart {
+        self.band = band
+            .iter()
+            .map(|(date_time, low, high)| {
+                (
+                    date_time.seconds_since_epoch() as f32 / 86_400.0,
+                    *low,
+                    *high,
+                )
+            })
+            .collect();
+        self
+    }
+
+    /// Marks `markers` (time, value) on the series, e.g. milestones.
+    pub fn with_markers(mut self, markers: &[(DateTime, f32)]) -> TrendChart {
+        self.markers = markers
+            .iter()
+            .map(|(date_time, v)| {
+                (
+                    date_time.seconds_since_epoch() as f32 / 86_400.0,
+                    *v,
+                    format!("{}: {}", date_time.date(), self.axis_label(*v)),
+                )
+            })
+            .collect();
+        self
+    }
+
+    /// Shades each break `(last before, first after, _)`, e.g. from `UserMeasurements::gaps`.
+    pub fn with_gaps(mut self, gaps: &[(DateTime, DateTime, u32)]) -> TrendChart {
+        let days = |date_time: &DateTime| date_time.seconds_since_epoch() as f32 / 86_400.0;
+        self.gaps = gaps
+            .iter()
+            .map(|(from, to, _)| (days(from), days(to)))
+            .collect();
+        self
+    }
+
+    /// Left and right edge of each break within `extent`, in plot coordinates.
+    fn gap_spans(&self, extent: (f32, f32, f32, f32), size: Size) -> Vec<(f32, f32)> {
+        let point = scaler(extent, size);
+        let (min_x, max_x, min_y, _) = extent;
+        self.gaps
+            .iter()
+            .filter(|(from, to)| *from >= min_x && *to <= max_x)
+            .map(|(from, to)| (point((*from, min_y)).x, point((*to, min_y)).x))
+            .collect()
+    }
+
+    /// Markers within `extent`, in plot coordinates, with their labels.
+    fn marker_points(&self, extent: (f32, f32, f32, f32), size: Size) -> Vec<(Point, &str)> {
+        let point = scaler(extent, size);
+        let (min_x, max_x, ..) = extent;
+        self.markers
+            .iter()
+            .filter(|(x, ..)| (min_x..=max_x).contains(x))
+            .map(|(x, y, label)| (point((*x, *y)), label.as_str()))
+            .collect()
+    }
+
+    /// Extent of the series widened to the healthy band over the same period.
+    fn plot_extent(&self) -> (f32, f32, f32, f32) {
+        let (min_x, max_x, min_y, max_y) = extent(&self.points);
+        self.band
+            .iter()
+            .filter(|(x, ..)| (min_x..=max_x).contains(x))
+            .fold(
+                (min_x, max_x, min_y, max_y),
+                |(lo_x, hi_x, lo_y, hi_y), (_, low, high)| {
+                    (lo_x, hi_x, lo_y.min(*low), hi_y.max(*high))
+                },
+            )
+    }
+
+    /// Outline of each stretch of the healthy band within `extent`: the high
+    /// edge forwards, then the low edge back. Gaps split it like the series.
+    fn band_polygons(&self, extent: (f32, f32, f32, f32), size: Size) -> Vec<Vec<Point>> {
+        let point = scaler(extent, size);
+        let (min_x, max_x, ..) = extent;
+        let mut runs: Vec<Vec<(f32, f32, f32)>> = Vec::new();
+        for &(x, low, high) in self
+            .band
+            .iter()
+            .filter(|(x, ..)| (min_x..=max_x).contains(x))
+        {
+            match runs.last_mut() {
+                Some(run)
+                    if run
+                        .last()
+                        .is_some_and(|(last_x, ..)| x - last_x <= MAX_TREND_GAP_DAYS) =>
+                {
+                    run.push((x, low, high))
+                }
+                _ => runs.push(vec![(x, low, high)]),
+            }
+        }
+        runs.into_iter()
+            .filter(|run| run.len() >= 2)
+            .map(|run| {
+                run.iter()
+                    .map(|(x, _, high)| point((*x, *high)))
+                    .chain(run.iter().rev().map(|(x, low, _)| point((*x, *low))))
+                    .collect()
+            })
+            .collect()
+    }
+
+    fn axis_label(&self, value: f32) -> String {
+        let value = self.units.convert_metric(self.metric, value);
+        format!(
+            "{} {}",
+            (value * 10.0).round() / 10.0,
+            self.units.metric_unit(self.metric)
+        )
+    }
+
+    /// The chart as a standalone SVG document, black on white for print,
+    /// with the same labels and gaps as on screen.
+    pub fn to_svg(&self) -> String {
+        let mut svg = format!(
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{w}\" height=\"{h}\" viewBox=\"0 0 {w} {h}\">\n\
+             <rect width=\"100%\" height=\"100%\" fill=\"white\"/>\n",
+            w = SVG_WIDTH,
+            h = SVG_HEIGHT
+        );
+        let label = |content: &str, y: f32, baseline: &str| {
+            format!(
+                "<text x=\"0\" y=\"{}\" font-family=\"sans-serif\" font-size=\"{}\" dominant-baseline=\"{}\">{}</text>\n",
+                y, AXIS_TEXT_SIZE, baseline, content
+            )
+        };
+
+        if self.points.is_empty() {
+            svg.push_str(&label(&format!("No {} data", self.metric), 0.0, "hanging"));
+        } else {
+            let plot_extent = self.plot_extent();
+            let (_, _, min_y, max_y) = plot_extent;
+            svg.push_str(&label(&self.axis_label(max_y), 0.0, "hanging"));
+            svg.push_str(&label(&self.metric.to_string(), SVG_HEIGHT / 2.0, "middle"));
+            svg.push_str(&label(
+                &self.axis_label(min_y),
+                SVG_HEIGHT,
+                "text-after-edge",
+            ));
+
+            let plot = Size::new(SVG_WIDTH - AXIS_LABEL_WIDTH, SVG_HEIGHT);
+            for (left, right) in self.gap_spans(plot_extent, plot) {
+                svg.push_str(&format!(
+                    "<rect x=\"{:.1}\" y=\"0\" width=\"{:.1}\" height=\"{}\" fill=\"grey\" fill-opacity=\"0.15\"/>\n",
+                    left + AXIS_LABEL_WIDTH,
+                    right - left,
+                    SVG_HEIGHT
+                ));
+            }
+            for polygon in self.band_polygons(plot_extent, plot) {
+                let coordinates: Vec<String> = polygon
+                    .iter()
+                    .map(|p| format!("{:.1},{:.1}", p.x + AXIS_LABEL_WIDTH, p.y))
+                    .collect();
+                svg.push_str(&format!(
+                    "<polygon points=\"{}\" fill=\"green\" fill-opacity=\"0.15\"/>\n",
+                    coordinates.join(" ")
+                ));
+            }
+            for segment in scaled_segments(&self.points, plot_extent, plot, MAX_TREND_GAP_DAYS) {
+                let coordinates: Vec<String> = segment
+                    .iter()
+                    .map(|p| format!("{:.1},{:.1}", p.x + AXIS_LABEL_WIDTH, p.y))
+                    .collect();
+                svg.push_str(&format!(
+                    "<polyline points=\"{}\" fill=\"none\" stroke=\"black\" stroke-width=\"1.5\"/>\n",
+                    coordinates.join(" ")
+                ));
+            }
+            // The <title> shows as a tooltip in browsers and SVG viewers.
+            for (p, label) in self.marker_points(plot_extent, plot) {
+                svg.push_str(&format!(
+                    "<g><title>{label}</title>\n\
+                     <line x1=\"{x:.1}\" y1=\"0\" x2=\"{x:.1}\" y2=\"{h}\" stroke=\"black\" stroke-opacity=\"0.4\" stroke-dasharray=\"4 3\"/>\n\
+                     <circle cx=\"{x:.1}\" cy=\"{y:.1}\" r=\"{r}\" fill=\"black\"/></g>\n",
+                    x = p.x + AXIS_LABEL_WIDTH,
+                    y = p.y,
+                    h = SVG_HEIGHT,
+                    r = MARKER_RADIUS
+                ));
+            }
+        }
+        svg.push_str("</svg>\n");
+        svg
+    }
+}
+
+impl<Message> canvas::Program<Message> for TrendChart {
+    type State = ();
+
+    fn draw(
+        &self,
+        _state: &Self::State,
+        renderer: &Renderer,
+        theme: &Theme,
+        bounds: Rectangle,
+        cursor: mouse::Cursor,
+    ) -> Vec<Geometry> {
+        let mut frame = Frame::new(renderer, bounds.size());
+        let color = theme.palette().text;
+        let label = |content: String, y: f32, vertical: alignment::Vertical| canvas::Text {
+            content,
+            position: Point::new(0.0, y),
+            color,
+            size: AXIS_TEXT_SIZE.into(),
+            vertical_alignment: vertical,
+            ..canvas::Text::default()
+        };
+
+        if self.points.is_empty() {
+            frame.fill_text(label(
+                format!("No {} data", self.metric),
+                0.0,
+                alignment::Vertical::Top,
+            ));
+            return vec![frame.into_geometry()];
+        }
+        let plot_extent = self.plot_extent();
+        let (_, _, min_y, max_y) = plot_extent;
+        frame.fill_text(label(self.axis_label(max_y), 0.0, alignment::Vertical::Top));
+        frame.fill_text(label(
+            self.metric.to_string(),
+            bounds.height / 2.0,
+            alignment::Vertical::Center,
+        ));
+        frame.fill_text(label(
+            self.axis_label(min_y),
+            bounds.height,
+            alignment::Vertical::Bottom,
+        ));
+
+        let plot = Size::new((bounds.width - AXIS_LABEL_WIDTH).max(0.0), bounds.height);
+        frame.translate(Vector::new(AXIS_LABEL_WIDTH, 0.0));
+        for (left, right) in self.gap_spans(plot_extent, plot) {
+            frame.fill_rectangle(
+                Point::new(left, 0.0),
+                Size::new(right - left, plot.height),
+                color.scale_alpha(0.08),
+            );
+        }
+        let band_color = theme.palette().success.scale_alpha(0.2);
+        for polygon in self.band_polygons(plot_extent, plot) {
+            let outline = Path::new(|builder| {
+                builder.move_to(polygon[0]);
+                for point in &polygon[1..] {
+                    builder.line_to(*point);
+                }
+                builder.close();
+            });
+            frame.fill(&outline, band_color);
+        }
+        if let Some(path) = line_path(&self.points, plot_extent, plot, MAX_TREND_GAP_DAYS) {
+            frame.stroke(
+                &path,
+                Stroke::default()
+                    .with_color(theme.palette().primary)
+                    .with_width(1.5),
+            );
+        }
+        let marker_color = theme.palette().danger;
+        let markers = self.marker_points(plot_extent, plot);
+        for (p, _) in &markers {
+            frame.stroke(
+                &Path::line(Point::new(p.x, 0.0), Point::new(p.x, plot.height)),
+                Stroke::default()
+                    .with_color(marker_color.scale_alpha(0.4))
+                    .with_width(1.0),
+            );
+            frame.fill(&Path::circle(*p, MARKER_RADIUS), marker_color);
+        }
+        let hovered = cursor.position_in(bounds).and_then(|position| {
+            markers
+                .iter()
+                .map(|(p, label)| ((p.x + AXIS_LABEL_WIDTH - position.x).abs(), p, label))
+                .filter(|(distance, ..)| *distance <= MARKER_HOVER_DISTANCE)
+                .min_by(|a, b| a.0.total_cmp(&b.0))
+        });
+        if let Some((_, p, label)) = hovered {
+            // Right of the line, or left of it near the right edge.
+            let (x, horizontal) = if p.x < plot.width / 2.0 {
+                (p.x + MARKER_RADIUS * 2.0, alignment::Horizontal::Left)
+            } else {
+                (p.x - MARKER_RADIUS * 2.0, alignment::Horizontal::Right)
+            };
+            frame.fill_text(canvas::Text {
+                content: label.to_string(),
+                position: Point::new(x, 0.0),
+                color: marker_color,
+                size: AXIS_TEXT_SIZE.into(),
+                horizontal_alignment: horizontal,
+                ..canvas::Text::default()
+            });
+        }
+        vec![frame.into_geometry()]
+    }
+}
+
+impl<Message> canvas::Program<Message> for Sparkline {
+    type State = ();
+
+    fn draw(
+        &self,
+        _state: &Self::State,
+        renderer: &Renderer,
+        theme: &Theme,
+        bounds: Rectangle,
+        _cursor: mouse::Cursor,
+    ) -> Vec<Geometry> {
+        let mut frame = Frame::new(renderer, bounds.size());
+        if let Some(path) = line_path(
+            &self.points,
+            extent(&self.points),
+            bounds.size(),
+            f32::INFINITY,
+        ) {
+            frame.stroke(
+                &path,
+                Stroke::default()
+                    .with_color(theme.palette().text)
+                    .with_width(1.0),
+            );
+        }
+        vec![frame.into_geometry()]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `values` one minute apart, from midnight on.
+    fn series(values: &[f32]) -> Vec<(DateTime, f32)> {
+        values
+            .iter()
+            .enumerate()
+            .map(|(minute, v)| {
+                let time = format!("{:02}:{:02}:00", minute / 60, minute % 60);
+                (DateTime::from_string("01/01/2020", &time).unwrap(), *v)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn short_series_is_returned_unchanged() {
+        let points = series(&[1.0, 2.0, 3.0]);
+        assert_eq!(downsample(&points, 3), points);
+        assert_eq!(downsample(&points, 10), points);
+    }
+
+    #[test]
+    fn long_series_keeps_exactly_max_points_and_both_ends() {
+        for (len, max_points) in [(1000, 100), (101, 100), (7, 5), (400, 3)] {
+            let values: Vec<f32> = (0..len).map(|i| (i % 7) as f32).collect();
+            let points = series(&values);
+            let sampled = downsample(&points, max_points);
+            assert_eq!(sampled.len(), max_points, "{} -> {}", len, max_points);
+            assert_eq!(sampled.first(), points.first());
+            assert_eq!(sampled.last(), points.last());
+            assert!(sampled.windows(2).all(|pair| pair[0].0 < pair[1].0));
+        }
+    }
+
+    #[test]
+    fn spike_in_a_flat_series_survives() {
+        for spike in [1, 537, 998] {
+            let mut values = vec![80.0; 1000];
+            values[spike] = 95.0;
+            let sampled = downsample(&series(&values), 50);
+            assert!(
+                sampled.iter().any(|(_, v)| *v == 95.0),
+                "spike at {}",
+                spike
+            );
+        }
+    }
+}
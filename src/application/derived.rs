@@ -0,0 +1,194 @@
+use super::Measurement;
+#[cfg(feature = "gui")]
+use super::general_data_structs::ActivityLevel;
+
+/// Computes a value from one measurement; `None` renders as `-`.
+#[cfg(feature = "gui")]
+pub type DeriveFn = Box<dyn Fn(&Measurement) -> Option<f32>>;
+
+/// An extra table column computed from each row. Held only in memory, so it
+/// never ends up in the settings file.
+#[cfg(feature = "gui")]
+pub struct DerivedColumn {
+    pub name: String,
+    pub compute: DeriveFn,
+}
+
+/// Weight times the fat percentage.
+pub fn fat_mass_kg(m: &Measurement) -> Option<f32> {
+    (m.weight_kg > 0.0 && !m.fat_percent.is_suspicious())
+        .then(|| m.weight_kg * m.fat_percent.value() / 100.0)
+}
+
+/// Weight minus fat mass.
+pub fn lean_mass_kg(m: &Measurement) -> Option<f32> {
+    Some(m.weight_kg - fat_mass_kg(m)?)
+}
+
+/// Basal metabolic rate by Katch-McArdle: 370 + 21.6 kcal per kg of lean
+/// mass. Uses only what the scale measured, so it needs no age or gender.
+#[cfg(feature = "gui")]
+pub fn bmr_kcal(m: &Measurement) -> Option<f32> {
+    Some(370.0 + 21.6 * lean_mass_kg(m)?)
+}
+
+/// Multiplier from BMR to total daily energy expenditure for the profile's
+/// activity level.
+#[cfg(feature = "gui")]
+fn activity_factor(activity_level: ActivityLevel) -> Option<f32> {
+    match activity_level {
+        ActivityLevel::Low => Some(1.375),
+        ActivityLevel::Medium => Some(1.55),
+        ActivityLevel::High => Some(1.725),
+        ActivityLevel::Other(_) => None,
+    }
+}
+
+/// Total daily energy expenditure: `bmr_kcal` times the activity factor.
+#[cfg(feature = "gui")]
+pub fn tdee_kcal(m: &Measurement) -> Option<f32> {
+    Some(bmr_kcal(m)? * activity_factor(ActivityLevel::from(m.activity_level_code))?)
+}
+
+/// Lean mass index of the echoed height, `None` without a height.
+pub fn lean_mass_index(m: &Measurement) -> Option<f32> {
+    (m.height_cm > 0.0).then_some(m.lean_mass_index(m.height_cm)?)
+}
+
+/// Fat mass index of the echoed height, `None` without a height.
+pub fn fat_mass_index(m: &Measurement) -> Option<f32> {
+    (m.height_cm > 0.0).then(|| m.fat_mass_index(m.height_cm))
+}
+
+/// Share of each segment in the sum of the five segmental fat readings.
+/// The scale reports no segment masses, so this compares fat percentages, not
+/// kilograms; it still shows where fat sits relative to the rest of the body.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FatDistribution {
+    pub trunk: f32,
+    pub right_arm: f32,
+    pub left_arm: f32,
+    pub right_leg: f32,
+    pub left_leg: f32,
+}
+
+impl FatDistribution {
+    /// Labelled shares in display order, summing to 1.
+    pub fn segments(&self) -> [(&'static str, f32); 5] {
+        [
+            ("Trunk", self.trunk),
+            ("R arm", self.right_arm),
+            ("L arm", self.left_arm),
+            ("R leg", self.right_leg),
+            ("L leg", self.left_leg),
+        ]
+    }
+}
+
+fn height_m_squared(height_cm: f32) -> f32 {
+    let height_m = height_cm / 100.0;
+    height_m * height_m
+}
+
+impl Measurement {
+    /// Fat mass index (FMI): fat mass over height squared, in kg/m².
+    /// `height_cm` must be positive.
+    pub fn fat_mass_index(&self, height_cm: f32) -> f32 {
+        self.weight_kg * self.fat_percent.value() / 100.0 / height_m_squared(height_cm)
+    }
+
+    /// `None` when a segmental reading is missing or out of range, or all of them are zero.
+    pub fn fat_distribution(&self) -> Option<FatDistribution> {
+        let readings = [
+            self.fat_trunk_pct?,
+            self.fat_right_arm_pct?,
+            self.fat_left_arm_pct?,
+            self.fat_right_leg_pct?,
+            self.fat_left_leg_pct?,
+        ];
+        if readings.iter().any(|p| p.is_suspicious()) {
+            return None;
+        }
+        let total: f32 = readings.iter().map(|p| p.value()).sum();
+        if total <= 0.0 {
+            return None;
+        }
+        let [trunk, right_arm, left_arm, right_leg, left_leg] = readings.map(|p| p.value() / total);
+        Some(FatDistribution {
+            trunk,
+            right_arm,
+            left_arm,
+            right_leg,
+            left_leg,
+        })
+    }
+
+    /// BMI worked out from the echoed weight and height, to check the device's
+    /// own `MI`. `None` without both.
+    pub fn computed_bmi(&self) -> Option<f32> {
+        (self.weight_kg > 0.0 && self.height_cm > 0.0)
+            .then(|| self.weight_kg / height_m_squared(self.height_cm))
+    }
+
+    /// Fat % over muscle %, lower is leaner. `None` without a muscle reading,
+    /// with a zero muscle reading or with either percentage out of range.
+    pub fn fat_muscle_ratio(&self) -> Option<f32> {
+        let muscle = self.muscle_percent.filter(|p| !p.is_suspicious())?.value();
+        (muscle > 0.0 && !self.fat_percent.is_suspicious())
+            .then(|| self.fat_percent.value() / muscle)
+    }
+
+    /// Lean body mass index (LBMI): lean mass over height squared, in kg/m².
+    /// `None` without a weight or with an out of range fat reading.
+    pub fn lean_mass_index(&self, height_cm: f32) -> Option<f32> {
+        Some(lean_mass_kg(self)? / height_m_squared(height_cm))
+    }
+
+    /// `fat_mass_kg`, 0.0 when the fat reading is zero, missing or out of range.
+    pub fn fat_mass_kg(&self) -> f32 {
+        self.with_fat_reading(fat_mass_kg)
+    }
+
+    /// `lean_mass_kg`, 0.0 when the fat reading is zero, missing or out of range.
+    pub fn lean_mass_kg(&self) -> f32 {
+        self.with_fat_reading(lean_mass_kg)
+    }
+
+    fn with_fat_reading(&self, mass_kg: fn(&Measurement) -> Option<f32>) -> f32 {
+        if self.fat_percent.value() > 0.0 {
+            mass_kg(self).unwrap_or(0.0)
+        } else {
+            0.0
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::application::parser::{DataRaw, ParserConfig, tests::with_checksum};
+
+    fn measurement(weight_and_fat: &str) -> Measurement {
+        let row = with_checksum(&format!(
+            r#"MO,"BC-601",DT,"05/01/2020",Ti,"09:07:00",{}"#,
+            weight_and_fat
+        ));
+        Measurement::from_raw(DataRaw::from_csv_row(&row, &ParserConfig::default())).unwrap()
+    }
+
+    #[test]
+    fn splits_weight_into_fat_and_lean_mass() {
+        let m = measurement("Wk,80.0,FW,20.0");
+        assert!((m.fat_mass_kg() - 16.0).abs() < 1e-4);
+        assert!((m.lean_mass_kg() - 64.0).abs() < 1e-4);
+    }
+
+    #[test]
+    fn masses_are_zero_without_a_fat_reading() {
+        for row in ["Wk,80.0,FW,0.0", "Wk,80.0,FW,abc", "Wk,80.0"] {
+            let m = measurement(row);
+            assert_eq!(m.fat_mass_kg(), 0.0, "{}", row);
+            assert_eq!(m.lean_mass_kg(), 0.0, "{}", row);
+        }
+    }
+}
@@ -0,0 +1,110 @@
+#[cfg(feature = "gui")]
+use std::collections::BTreeMap;
+
+use super::UserMeasurements;
+
+/// How many distinct example values are kept per unknown tag.
+#[cfg(feature = "gui")]
+const MAX_SAMPLES_PER_TAG: usize = 3;
+/// Only this many of the earliest readings can be taken for test weigh-ins.
+const MAX_TEST_READINGS: usize = 2;
+/// Readings after the candidates needed before any are flagged.
+const MIN_REFERENCE_READINGS: usize = 5;
+/// A candidate is flagged when it weighs under this share of the later median.
+const TEST_WEIGHT_RATIO: f32 = 0.5;
+
+/// Every tag that ended up in `extras`, with how often it was seen and a few
+/// sample values. Contains no dates or identifying data, so it is safe to share.
+#[cfg(feature = "gui")]
+pub fn unknown_tag_report(users: &[UserMeasurements]) -> BTreeMap<String, (usize, Vec<String>)> {
+    let mut report: BTreeMap<String, (usize, Vec<String>)> = BTreeMap::new();
+    for user in users {
+        for measurement in &user.measurements {
+            for (key, value) in &measurement.extras {
+                let (count, samples) = report.entry(key.clone()).or_default();
+                *count += 1;
+                if samples.len() < MAX_SAMPLES_PER_TAG && !samples.contains(value) {
+                    samples.push(value.clone());
+                }
+            }
+        }
+    }
+    report
+}
+
+/// One line per DATA value that didn't read as a number and was taken as 0,
+/// e.g. `User 1, line 12: Wk="7o.2": invalid float literal`. Lines are
+/// 1-based, as in a text editor.
+#[cfg(feature = "gui")]
+pub fn parse_warning_report(users: &[UserMeasurements]) -> String {
+    let mut report = String::new();
+    for user in users {
+        for measurement in &user.measurements {
+            for warning in &measurement.parse_warnings {
+                report.push_str(&format!(
+                    "User {}, line {}: {}\n",
+                    user.index + 1,
+                    measurement.source_line,
+                    warning
+                ));
+            }
+        }
+    }
+    report
+}
+
+/// Groups of user indices whose profiles share birth date, gender and height,
+/// likely the same person set up twice. Each group is sorted and has two or more users.
+#[cfg(feature = "gui")]
+pub fn find_duplicate_profiles(users: &[UserMeasurements]) -> Vec<Vec<usize>> {
+    let mut groups: Vec<Vec<&UserMeasurements>> = Vec::new();
+    for user in users {
+        let same_profile = |other: &&UserMeasurements| {
+            other.profile.birth_date_dmy == user.profile.birth_date_dmy
+                && other.profile.gender == user.profile.gender
+                && other.profile.height_cm == user.profile.height_cm
+        };
+        match groups.iter_mut().find(|group| same_profile(&group[0])) {
+            Some(group) => group.push(user),
+            None => groups.push(vec![user]),
+        }
+    }
+    groups
+        .into_iter()
+        .filter(|group| group.len() > 1)
+        .map(|group| {
+            let mut indices: Vec<usize> = group.iter().map(|u| u.index).collect();
+            indices.sort();
+            indices
+        })
+        .collect()
+}
+
+impl UserMeasurements {
+    /// Indices into `measurements` of the earliest readings that look like test
+    /// weigh-ins on a fresh device: at most the first two not excluded ones, each
+    /// under half the median weight of the rest. Stops at the first plausible
+    /// reading and flags nothing without five later readings to compare with.
+    pub fn likely_test_readings(&self) -> Vec<usize> {
+        let mut order: Vec<usize> = (0..self.measurements.len())
+            .filter(|&i| !self.measurements[i].excluded)
+            .collect();
+        order.sort_by_key(|&i| &self.measurements[i]);
+        if order.len() < MAX_TEST_READINGS + MIN_REFERENCE_READINGS {
+            return Vec::new();
+        }
+
+        let mut reference: Vec<f32> = order[MAX_TEST_READINGS..]
+            .iter()
+            .map(|&i| self.measurements[i].weight_kg)
+            .collect();
+        reference.sort_by(f32::total_cmp);
+        let median = reference[reference.len() / 2];
+
+        order[..MAX_TEST_READINGS]
+            .iter()
+            .copied()
+            .take_while(|&i| self.measurements[i].weight_kg < median * TEST_WEIGHT_RATIO)
+            .collect()
+    }
+}
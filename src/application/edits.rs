@@ -0,0 +1,264 @@
+use std::fmt;
+#[cfg(feature = "gui")]
+use std::{collections::BTreeMap, fs, path::Path};
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+#[cfg(feature = "gui")]
+use super::general_data_structs::{DateTime, Percent};
+#[cfg(feature = "gui")]
+use super::{Measurement, UserMeasurements};
+
+/// Written next to DATA/SYSTEM so the device files are never touched.
+#[cfg(feature = "gui")]
+const SIDECAR_FILE_NAME: &str = "tanita-601-edits.json";
+
+/// Measurement values that can be corrected by hand.
+// Only the viewer makes overrides; without it just `WeightKg` is looked up.
+#[cfg_attr(not(feature = "gui"), allow(dead_code))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum Field {
+    WeightKg,
+    Bmi,
+    FatPercent,
+    MusclePercent,
+    BoneKg,
+    WaterPercent,
+}
+
+impl Field {
+    #[cfg(feature = "serde")]
+    pub const ALL: [Field; 6] = [
+        Field::WeightKg,
+        Field::Bmi,
+        Field::FatPercent,
+        Field::MusclePercent,
+        Field::BoneKg,
+        Field::WaterPercent,
+    ];
+
+    #[cfg(feature = "gui")]
+    pub fn get(&self, m: &Measurement) -> Option<f32> {
+        match self {
+            Field::WeightKg => Some(m.weight_kg),
+            Field::Bmi => Some(m.bmi),
+            Field::FatPercent => Some(m.fat_percent.value()),
+            Field::MusclePercent => m.muscle_percent.map(|p| p.value()),
+            Field::BoneKg => m.bone_kg,
+            Field::WaterPercent => m.water_percent.map(|p| p.value()),
+        }
+    }
+
+    #[cfg(feature = "gui")]
+    fn set(&self, m: &mut Measurement, value: Option<f32>) {
+        match self {
+            Field::WeightKg => m.weight_kg = value.unwrap_or_default(),
+            Field::Bmi => m.bmi = value.unwrap_or_default(),
+            Field::FatPercent => m.fat_percent = Percent::new(value.unwrap_or_default()),
+            Field::MusclePercent => m.muscle_percent = value.map(Percent::new),
+            Field::BoneKg => m.bone_kg = value,
+            Field::WaterPercent => m.water_percent = value.map(Percent::new),
+        }
+    }
+}
+
+impl fmt::Display for Field {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Field::WeightKg => write!(f, "Weight (kg)"),
+            Field::Bmi => write!(f, "BMI"),
+            Field::FatPercent => write!(f, "Fat (%)"),
+            Field::MusclePercent => write!(f, "Muscle (%)"),
+            Field::BoneKg => write!(f, "Bones (kg)"),
+            Field::WaterPercent => write!(f, "Water (%)"),
+        }
+    }
+}
+
+/// Hand corrections for one measurement, identified by user index and
+/// `Measurement::fingerprint`. Entries from older sidecars have only the
+/// timestamp; they are matched by it once and get their fingerprint filled in.
+#[cfg(feature = "gui")]
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+struct MeasurementEdit {
+    user: usize,
+    /// `Measurement::fingerprint`; absent in sidecars written before it existed.
+    #[cfg_attr(feature = "serde", serde(default))]
+    fingerprint: Option<u64>,
+    /// Kept so the file stays readable by hand, and to match older entries.
+    #[cfg_attr(feature = "serde", serde(default))]
+    date_time: Option<DateTime>,
+    #[cfg_attr(feature = "serde", serde(default))]
+    excluded: bool,
+    /// Starred by hand as a milestone (goal reached, event day).
+    #[cfg_attr(feature = "serde", serde(default))]
+    milestone: bool,
+    #[cfg_attr(feature = "serde", serde(default))]
+    overrides: BTreeMap<Field, f32>,
+}
+
+/// Every hand edit of a card, kept in its sidecar file.
+#[cfg(feature = "gui")]
+#[derive(Debug, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Edits {
+    measurements: Vec<MeasurementEdit>,
+}
+
+#[cfg(feature = "gui")]
+impl Edits {
+    /// Reads the sidecar from `root`; a missing or unreadable one means no
+    /// edits. A malformed one is an error, for the caller to report before
+    /// carrying on with `Edits::default()`.
+    pub fn load(root: &Path) -> Result<Edits, String> {
+        let Ok(content) = fs::read_to_string(root.join(SIDECAR_FILE_NAME)) else {
+            return Ok(Edits::default());
+        };
+        serde_json::from_str(&content)
+            .map_err(|err| format!("Ignoring malformed {}: {}", SIDECAR_FILE_NAME, err))
+    }
+
+    pub fn save(&self, root: &Path) -> Result<(), String> {
+        let json = serde_json::to_string_pretty(self).map_err(|err| err.to_string())?;
+        fs::write(root.join(SIDECAR_FILE_NAME), json).map_err(|err| err.to_string())
+    }
+
+    /// Applies every stored edit to freshly parsed measurements. Older entries
+    /// keyed only by timestamp get their fingerprint filled in on the way.
+    pub fn apply_all(&mut self, users: &mut [UserMeasurements]) {
+        for edit in &mut self.measurements {
+            let m = match (edit.fingerprint, &edit.date_time) {
+                (Some(fingerprint), _) => find_measurement(users, edit.user, fingerprint),
+                (None, Some(date_time)) => users
+                    .iter_mut()
+                    .find(|u| u.index == edit.user)
+                    .and_then(|u| {
+                        u.measurements
+                            .iter_mut()
+                            .find(|m| m.date_time.as_ref() == Some(date_time))
+                    }),
+                (None, None) => None,
+            };
+            let Some(m) = m else {
+                continue;
+            };
+            edit.fingerprint = Some(m.fingerprint());
+            m.excluded = edit.excluded;
+            m.milestone = edit.milestone;
+            for (field, value) in &edit.overrides {
+                override_value(m, *field, Some(*value));
+            }
+        }
+    }
+
+    pub fn set_excluded(
+        &mut self,
+        users: &mut [UserMeasurements],
+        user: usize,
+        fingerprint: u64,
+        excluded: bool,
+    ) {
+        if let Some(m) = find_measurement(users, user, fingerprint) {
+            m.excluded = excluded;
+            let date_time = m.date_time.clone();
+            self.entry(user, fingerprint, date_time).excluded = excluded;
+        }
+    }
+
+    pub fn set_milestone(
+        &mut self,
+        users: &mut [UserMeasurements],
+        user: usize,
+        fingerprint: u64,
+        milestone: bool,
+    ) {
+        if let Some(m) = find_measurement(users, user, fingerprint) {
+            m.milestone = milestone;
+            let date_time = m.date_time.clone();
+            self.entry(user, fingerprint, date_time).milestone = milestone;
+        }
+    }
+
+    /// Replaces `field` with `value`, or restores the device value when `value` is `None`.
+    pub fn set_override(
+        &mut self,
+        users: &mut [UserMeasurements],
+        user: usize,
+        fingerprint: u64,
+        field: Field,
+        value: Option<f32>,
+    ) {
+        let Some(m) = find_measurement(users, user, fingerprint) else {
+            return;
+        };
+        override_value(m, field, value);
+        let date_time = m.date_time.clone();
+
+        let edit = self.entry(user, fingerprint, date_time);
+        match value {
+            Some(v) => edit.overrides.insert(field, v),
+            None => edit.overrides.remove(&field),
+        };
+    }
+
+    fn entry(
+        &mut self,
+        user: usize,
+        fingerprint: u64,
+        date_time: Option<DateTime>,
+    ) -> &mut MeasurementEdit {
+        let position = self
+            .measurements
+            .iter()
+            .position(|e| e.user == user && e.fingerprint == Some(fingerprint));
+        let position = position.unwrap_or_else(|| {
+            self.measurements.push(MeasurementEdit {
+                user,
+                fingerprint: Some(fingerprint),
+                date_time,
+                excluded: false,
+                milestone: false,
+                overrides: BTreeMap::new(),
+            });
+            self.measurements.len() - 1
+        });
+        &mut self.measurements[position]
+    }
+}
+
+#[cfg(feature = "gui")]
+fn find_measurement(
+    users: &mut [UserMeasurements],
+    user: usize,
+    fingerprint: u64,
+) -> Option<&mut Measurement> {
+    users
+        .iter_mut()
+        .find(|u| u.index == user)?
+        .measurements
+        .iter_mut()
+        .find(|m| m.fingerprint() == fingerprint)
+}
+
+/// Keeps the device value in `original` the first time a field is overridden,
+/// so clearing the override can put it back.
+#[cfg(feature = "gui")]
+fn override_value(m: &mut Measurement, field: Field, value: Option<f32>) {
+    match value {
+        Some(v) => {
+            if !m.original.contains_key(&field) {
+                let device_value = field.get(m);
+                m.original.insert(field, device_value);
+            }
+            field.set(m, Some(v));
+        }
+        None => {
+            if let Some(device_value) = m.original.remove(&field) {
+                field.set(m, device_value);
+            }
+        }
+    }
+}
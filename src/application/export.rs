@@ -0,0 +1,352 @@
+use std::{borrow::Cow, fmt};
+#[cfg(feature = "gui")]
+use std::{fs, path::PathBuf};
+
+#[cfg(feature = "gui")]
+use rfd::AsyncFileDialog;
+
+use super::derived;
+use super::field_tag::FieldTag;
+use super::general_data_structs::{DateTime, Percent};
+use super::units::Units;
+use super::{MISSING_VALUE, Measurement, UserMeasurements};
+
+/// Column set of a per-user CSV export.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportPreset {
+    /// Every field kept on a measurement, labelled as in `Measurement::to_map`,
+    /// followed by the derived composition indices. The only preset that
+    /// follows the export units.
+    All,
+    /// Fixed headers and order that paste into the body-composition trend
+    /// spreadsheet. Changing them breaks existing sheets, so it stays in kg.
+    TrendTemplate,
+    /// openScale's CSV import/export layout, always in kg as openScale reads
+    /// it. Columns we don't measure are left empty, and rows taken with an
+    /// unset clock are skipped as openScale needs a timestamp.
+    OpenScale,
+}
+
+const DERIVED_HEADERS: [&str; 3] = ["Fat mass index", "Lean mass index", "Fat/muscle ratio"];
+
+const TREND_TEMPLATE_HEADERS: [&str; 7] = [
+    "Date",
+    "Weight (kg)",
+    "Fat (%)",
+    "Muscle (%)",
+    "Visceral fat",
+    "Metabolic age",
+    "Water (%)",
+];
+
+/// Header of openScale's CSV files, in its order.
+const OPEN_SCALE_HEADERS: [&str; 19] = [
+    "dateTime",
+    "weight",
+    "fat",
+    "water",
+    "muscle",
+    "visceralFat",
+    "lbm",
+    "bone",
+    "waist",
+    "hip",
+    "chest",
+    "thigh",
+    "biceps",
+    "neck",
+    "caliper1",
+    "caliper2",
+    "caliper3",
+    "calories",
+    "comment",
+];
+
+/// `field` as an RFC 4180 CSV field: wrapped in quotes, with inner quotes
+/// doubled, when it holds a comma, quote or line break; otherwise as is.
+pub fn csv_escape(field: &str) -> Cow<'_, str> {
+    if field.contains([',', '"', '\n', '\r']) {
+        Cow::Owned(format!("\"{}\"", field.replace('"', "\"\"")))
+    } else {
+        Cow::Borrowed(field)
+    }
+}
+
+/// One CSV line with every field passed through `csv_escape`, newline included.
+pub fn csv_record<S: AsRef<str>>(fields: impl IntoIterator<Item = S>) -> String {
+    let mut line = fields
+        .into_iter()
+        .map(|field| csv_escape(field.as_ref()).into_owned())
+        .collect::<Vec<String>>()
+        .join(",");
+    line.push('\n');
+    line
+}
+
+/// `dd.MM.yyyy HH:mm`, the timestamp format openScale reads.
+fn open_scale_date_time(date_time: &DateTime) -> String {
+    let (date, time) = (date_time.date(), date_time.time());
+    format!(
+        "{:02}.{:02}.{:04} {:02}:{:02}",
+        date.day(),
+        date.month(),
+        date.year(),
+        time.hours(),
+        time.minutes()
+    )
+}
+
+impl ExportPreset {
+    pub const ALL: [ExportPreset; 3] = [
+        ExportPreset::All,
+        ExportPreset::TrendTemplate,
+        ExportPreset::OpenScale,
+    ];
+
+    pub fn file_name(&self, user: usize) -> String {
+        match self {
+            ExportPreset::All => format!("user{}.csv", user + 1),
+            ExportPreset::TrendTemplate => format!("user{}_trend_template.csv", user + 1),
+            ExportPreset::OpenScale => format!("user{}_openscale.csv", user + 1),
+        }
+    }
+
+    /// Depends only on the preset, so an export with no rows still has one.
+    fn headers(&self, units: Units) -> Vec<Cow<'static, str>> {
+        let fixed = |headers: &[&'static str]| headers.iter().map(|h| Cow::Borrowed(*h)).collect();
+        match self {
+            ExportPreset::All => Measurement::map_labels(units)
+                .into_iter()
+                .chain(DERIVED_HEADERS.map(Cow::Borrowed))
+                .collect(),
+            ExportPreset::TrendTemplate => fixed(&TREND_TEMPLATE_HEADERS),
+            ExportPreset::OpenScale => fixed(&OPEN_SCALE_HEADERS),
+        }
+    }
+
+    /// Missing values are empty cells so spreadsheets treat them as blanks.
+    /// `None` for rows the preset can't represent.
+    fn row(&self, m: &Measurement, units: Units) -> Option<Vec<String>> {
+        fn cell<T: ToString>(value: Option<T>) -> String {
+            value.map(|v| v.to_string()).unwrap_or_default()
+        }
+        fn pct(value: Option<Percent>) -> String {
+            cell(value.map(|p| p.value()))
+        }
+
+        let row = match self {
+            ExportPreset::All => {
+                let mut row: Vec<String> = m
+                    .to_map(units)
+                    .into_iter()
+                    .map(|(_, value)| {
+                        if value == MISSING_VALUE {
+                            String::new()
+                        } else {
+                            value
+                        }
+                    })
+                    .collect();
+                row.extend([
+                    cell(derived::fat_mass_index(m)),
+                    cell(derived::lean_mass_index(m)),
+                    cell(m.fat_muscle_ratio()),
+                ]);
+                row
+            }
+            ExportPreset::TrendTemplate => vec![
+                cell(m.date_time.as_ref().map(|dt| dt.date().format_iso())),
+                m.weight_kg.to_string(),
+                pct(Some(m.fat_percent)),
+                pct(m.muscle_percent),
+                cell(m.visceral_fat_rating),
+                cell(m.metabolic_age_years),
+                pct(m.water_percent),
+            ],
+            ExportPreset::OpenScale => {
+                let mut row = vec![
+                    open_scale_date_time(m.date_time.as_ref()?),
+                    m.weight_kg.to_string(),
+                    pct(Some(m.fat_percent)),
+                    pct(m.water_percent),
+                    pct(m.muscle_percent),
+                    cell(m.visceral_fat_rating),
+                    cell(derived::lean_mass_kg(m)),
+                    cell(m.bone_kg),
+                ];
+                row.resize(OPEN_SCALE_HEADERS.len(), String::new());
+                row
+            }
+        };
+        Some(row)
+    }
+}
+
+impl fmt::Display for ExportPreset {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ExportPreset::All => write!(f, "CSV"),
+            ExportPreset::TrendTemplate => write!(f, "trend-template"),
+            ExportPreset::OpenScale => write!(f, "openScale"),
+        }
+    }
+}
+
+impl UserMeasurements {
+    /// Measurements not excluded by hand, oldest first, with the preset's columns.
+    /// Rows the preset can't represent are skipped.
+    pub fn export_csv(&self, preset: ExportPreset, units: Units) -> String {
+        let mut rows: Vec<&Measurement> =
+            self.measurements.iter().filter(|m| !m.excluded).collect();
+        rows.sort();
+        csv_of(&rows, preset, units)
+    }
+
+    /// Only the starred measurements, with every column.
+    pub fn export_milestones(&self, units: Units) -> String {
+        csv_of(&self.milestones(), ExportPreset::All, units)
+    }
+}
+
+/// `rows`, already sorted, under the preset's header.
+fn csv_of(rows: &[&Measurement], preset: ExportPreset, units: Units) -> String {
+    let mut out = csv_record(preset.headers(units));
+    for row in rows.iter().filter_map(|m| preset.row(m, units)) {
+        out.push_str(&csv_record(row));
+    }
+    out
+}
+
+impl UserMeasurements {
+    /// Not excluded measurements taken strictly after `after`, oldest first.
+    /// Undated ones sort before every date, so they are never included.
+    pub fn since(&self, after: &DateTime) -> Vec<&Measurement> {
+        let mut rows: Vec<&Measurement> = self
+            .measurements
+            .iter()
+            .filter(|m| !m.excluded && m.date_time.as_ref().is_some_and(|dt| dt > after))
+            .collect();
+        rows.sort();
+        rows
+    }
+}
+
+/// Every dated, not excluded measurement of all users as one stream:
+/// `datetime,user_index,weight_kg` (timestamps as `DateTime::format_iso`),
+/// sorted by time and then user. The user index is numbered from 1, as on
+/// the tabs.
+pub fn export_household_timeline(users: &[UserMeasurements]) -> String {
+    export_household_timeline_since(users, None, Units::Metric)
+}
+
+/// `export_household_timeline` in `units` (`weight_lb` in imperial). With
+/// `after`, only measurements taken strictly later are included, for an
+/// incremental export.
+pub fn export_household_timeline_since(
+    users: &[UserMeasurements],
+    after: Option<&DateTime>,
+    units: Units,
+) -> String {
+    let mut entries: Vec<(&DateTime, usize, f32)> = users
+        .iter()
+        .flat_map(|u| {
+            let rows: Vec<&Measurement> = match after {
+                Some(after) => u.since(after),
+                None => u.measurements.iter().filter(|m| !m.excluded).collect(),
+            };
+            rows.into_iter()
+                .filter_map(|m| Some((m.date_time.as_ref()?, u.index, m.weight_kg)))
+        })
+        .collect();
+    entries.sort_by(|a, b| a.0.cmp(b.0).then(a.1.cmp(&b.1)));
+
+    let weight_header = match units {
+        Units::Metric => "weight_kg",
+        Units::Imperial => "weight_lb",
+    };
+    let mut out = csv_record(["datetime", "user_index", weight_header]);
+    for (date_time, user, weight_kg) in entries {
+        out.push_str(&csv_record([
+            date_time.format_iso(),
+            (user + 1).to_string(),
+            units.convert(FieldTag::Weight, weight_kg).to_string(),
+        ]));
+    }
+    out
+}
+
+/// Asks for a destination and writes `contents` there.
+/// `Ok(None)` means the dialog was cancelled.
+#[cfg(feature = "gui")]
+pub async fn save_to_file(
+    default_name: String,
+    contents: String,
+) -> Result<Option<PathBuf>, String> {
+    let Some(file_handle) = AsyncFileDialog::new()
+        .set_file_name(&default_name)
+        .save_file()
+        .await
+    else {
+        return Ok(None);
+    };
+
+    let path: PathBuf = file_handle.into();
+    fs::write(&path, contents).map_err(|err| format!("{}: {}", path.display(), err))?;
+    Ok(Some(path))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::application::tests::user;
+
+    #[test]
+    fn csv_escape_quotes_only_when_needed() {
+        assert_eq!(csv_escape("plain"), "plain");
+        assert_eq!(csv_escape("a,b"), "\"a,b\"");
+        assert_eq!(csv_escape(r#"say "hi""#), r#""say ""hi""""#);
+        assert_eq!(csv_escape("two\nlines"), "\"two\nlines\"");
+        assert_eq!(csv_escape("cr\rlf"), "\"cr\rlf\"");
+    }
+
+    #[test]
+    fn csv_record_joins_escaped_fields() {
+        assert_eq!(
+            csv_record(["note, with comma", "6\" tall", "", "ok"]),
+            "\"note, with comma\",\"6\"\" tall\",,ok\n"
+        );
+    }
+
+    #[test]
+    fn empty_export_still_has_the_header() {
+        for preset in ExportPreset::ALL {
+            let csv = csv_of(&[], preset, Units::Metric);
+            assert_eq!(csv, csv_record(preset.headers(Units::Metric)));
+            assert!(csv.len() > 1, "{} header is empty", preset);
+        }
+    }
+
+    #[test]
+    fn household_timeline_interleaves_users_by_time() {
+        let users = [
+            user(0, &[("01/01/2020", 80.0), ("03/01/2020", 79.5)]),
+            user(1, &[("02/01/2020", 60.0), ("03/01/2020", 60.5)]),
+        ];
+        assert_eq!(
+            export_household_timeline(&users),
+            "datetime,user_index,weight_kg\n\
+             2020-01-01T08:00:00,1,80\n\
+             2020-01-02T08:00:00,2,60\n\
+             2020-01-03T08:00:00,1,79.5\n\
+             2020-01-03T08:00:00,2,60.5\n"
+        );
+
+        let after = users[1].measurements[0].date_time.clone().unwrap();
+        assert_eq!(
+            export_household_timeline_since(&users, Some(&after), Units::Metric)
+                .lines()
+                .count(),
+            3
+        );
+    }
+}
@@ -0,0 +1,173 @@
+/// Every two-letter tag the BC-601 family writes into DATA/PROF rows.
+/// Phase angle and impedance are not listed: their tags are set in `ParserConfig`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum FieldTag {
+    Model,
+    BirthDate,
+    Date,
+    Time,
+    Gender,
+    Age,
+    Height,
+    ActivityLevel,
+    BodyType,
+    Weight,
+    Bmi,
+    FatPercent,
+    FatRightArm,
+    FatLeftArm,
+    FatRightLeg,
+    FatLeftLeg,
+    FatTrunk,
+    MusclePercent,
+    MuscleRightArm,
+    MuscleLeftArm,
+    MuscleRightLeg,
+    MuscleLeftLeg,
+    MuscleTrunk,
+    BoneMass,
+    WaterPercent,
+    VisceralFat,
+    MetabolicAge,
+    DailyCalorieIntake,
+    Checksum,
+}
+
+impl FieldTag {
+    pub const ALL: [FieldTag; 29] = [
+        FieldTag::Model,
+        FieldTag::BirthDate,
+        FieldTag::Date,
+        FieldTag::Time,
+        FieldTag::Gender,
+        FieldTag::Age,
+        FieldTag::Height,
+        FieldTag::ActivityLevel,
+        FieldTag::BodyType,
+        FieldTag::Weight,
+        FieldTag::Bmi,
+        FieldTag::FatPercent,
+        FieldTag::FatRightArm,
+        FieldTag::FatLeftArm,
+        FieldTag::FatRightLeg,
+        FieldTag::FatLeftLeg,
+        FieldTag::FatTrunk,
+        FieldTag::MusclePercent,
+        FieldTag::MuscleRightArm,
+        FieldTag::MuscleLeftArm,
+        FieldTag::MuscleRightLeg,
+        FieldTag::MuscleLeftLeg,
+        FieldTag::MuscleTrunk,
+        FieldTag::BoneMass,
+        FieldTag::WaterPercent,
+        FieldTag::VisceralFat,
+        FieldTag::MetabolicAge,
+        FieldTag::DailyCalorieIntake,
+        FieldTag::Checksum,
+    ];
+
+    /// `None` for tags this enum doesn't know about (they end up in `extras`).
+    pub fn from_code(code: &str) -> Option<FieldTag> {
+        FieldTag::ALL.into_iter().find(|tag| tag.code() == code)
+    }
+
+    /// The tag as written in the CSV row (case-sensitive).
+    pub fn code(&self) -> &'static str {
+        match self {
+            FieldTag::Model => "MO",
+            FieldTag::BirthDate => "DB",
+            FieldTag::Date => "DT",
+            FieldTag::Time => "Ti",
+            FieldTag::Gender => "GE",
+            FieldTag::Age => "AG",
+            FieldTag::Height => "Hm",
+            FieldTag::ActivityLevel => "AL",
+            FieldTag::BodyType => "Bt",
+            FieldTag::Weight => "Wk",
+            FieldTag::Bmi => "MI",
+            FieldTag::FatPercent => "FW",
+            FieldTag::FatRightArm => "Fr",
+            FieldTag::FatLeftArm => "Fl",
+            FieldTag::FatRightLeg => "FR",
+            FieldTag::FatLeftLeg => "FL",
+            FieldTag::FatTrunk => "FT",
+            FieldTag::MusclePercent => "mW",
+            FieldTag::MuscleRightArm => "mr",
+            FieldTag::MuscleLeftArm => "ml",
+            FieldTag::MuscleRightLeg => "mR",
+            FieldTag::MuscleLeftLeg => "mL",
+            FieldTag::MuscleTrunk => "mT",
+            FieldTag::BoneMass => "bw",
+            FieldTag::WaterPercent => "ww",
+            FieldTag::VisceralFat => "IF",
+            FieldTag::MetabolicAge => "rA",
+            FieldTag::DailyCalorieIntake => "rD",
+            FieldTag::Checksum => "CS",
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            FieldTag::Model => "Model",
+            FieldTag::BirthDate => "Birth date",
+            FieldTag::Date => "Date",
+            FieldTag::Time => "Time",
+            FieldTag::Gender => "Gender",
+            FieldTag::Age => "Age",
+            FieldTag::Height => "Height",
+            FieldTag::ActivityLevel => "Activity level",
+            FieldTag::BodyType => "Body level",
+            FieldTag::Weight => "Weight",
+            FieldTag::Bmi => "BMI",
+            FieldTag::FatPercent => "Fat",
+            FieldTag::FatRightArm => "Fat r arm",
+            FieldTag::FatLeftArm => "Fat l arm",
+            FieldTag::FatRightLeg => "Fat r leg",
+            FieldTag::FatLeftLeg => "Fat l leg",
+            FieldTag::FatTrunk => "Fat torso",
+            FieldTag::MusclePercent => "Muscle",
+            FieldTag::MuscleRightArm => "Muscle r arm",
+            FieldTag::MuscleLeftArm => "Muscle l arm",
+            FieldTag::MuscleRightLeg => "Muscle r leg",
+            FieldTag::MuscleLeftLeg => "Muscle l leg",
+            FieldTag::MuscleTrunk => "Muscle torso",
+            FieldTag::BoneMass => "Bones",
+            FieldTag::WaterPercent => "Water",
+            FieldTag::VisceralFat => "Visceral fat rating",
+            FieldTag::MetabolicAge => "Metabolic age",
+            FieldTag::DailyCalorieIntake => "Daily calorie intake",
+            FieldTag::Checksum => "Checksum",
+        }
+    }
+
+    pub fn unit(&self) -> Option<&'static str> {
+        match self {
+            FieldTag::Height => Some("cm"),
+            FieldTag::Weight | FieldTag::BoneMass => Some("kg"),
+            FieldTag::FatPercent
+            | FieldTag::FatRightArm
+            | FieldTag::FatLeftArm
+            | FieldTag::FatRightLeg
+            | FieldTag::FatLeftLeg
+            | FieldTag::FatTrunk
+            | FieldTag::MusclePercent
+            | FieldTag::MuscleRightArm
+            | FieldTag::MuscleLeftArm
+            | FieldTag::MuscleRightLeg
+            | FieldTag::MuscleLeftLeg
+            | FieldTag::MuscleTrunk
+            | FieldTag::WaterPercent => Some("%"),
+            FieldTag::Age | FieldTag::MetabolicAge => Some("years"),
+            FieldTag::DailyCalorieIntake => Some("kcal"),
+            _ => None,
+        }
+    }
+
+    /// Column header, e.g. "Weight (kg)".
+    pub fn header(&self) -> String {
+        match self.unit() {
+            Some(unit) => format!("{} ({})", self.label(), unit),
+            None => self.label().to_string(),
+        }
+    }
+}
@@ -1,11 +1,26 @@
-#[derive(Debug)]
+use std::{error::Error, fmt, str::FromStr};
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// Fields are declared most significant first: the derived `Ord` relies on it.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Date {
     years: u16,
     months: u8,
     days: u8,
 }
 
+/// What the scale writes as `DT` when its clock was never set.
+const UNSET_CLOCK_DATE: &str = "00/00/0000";
+
 impl Date {
+    /// `true` for the placeholder date written before the clock was set.
+    pub fn is_unset_placeholder(date_dmy: &str) -> bool {
+        date_dmy.trim_matches('"') == UNSET_CLOCK_DATE
+    }
+
     pub fn from_string(date_dmy: &str) -> Option<Date> {
         let mut iterator = date_dmy.trim_matches('"').split('/');
         let d = iterator.next()?;
@@ -13,7 +28,6 @@ impl Date {
         let y = iterator.next()?;
 
         if iterator.next().is_some() {
-            println!("DATE PARSIG HAS SOME EXTRA VALUE");
             return None;
         }
 
@@ -21,19 +35,61 @@ impl Date {
         let months = m.parse::<u8>().ok()?;
         let years = y.parse::<u16>().ok()?;
 
-        return Some(Date {
+        Some(Date {
             days,
             months,
             years,
-        });
+        })
     }
 
+    #[deprecated(note = "use `to_string`, which writes the device's `dd/mm/yyyy`")]
     pub fn to_srting(&self) -> String {
-        format!("{}/{}/{}", self.years, self.months, self.days)
+        self.to_string()
+    }
+
+    /// `yyyy-mm-dd`, for exports read by other programs.
+    pub fn format_iso(&self) -> String {
+        format!("{:04}-{:02}-{:02}", self.years, self.months, self.days)
+    }
+
+    pub fn year(&self) -> u16 {
+        self.years
+    }
+
+    pub fn month(&self) -> u8 {
+        self.months
+    }
+
+    pub fn day(&self) -> u8 {
+        self.days
+    }
+
+    /// Whole years from `self` to `later`, i.e. the age on `later` for a birth
+    /// date. `None` when `later` comes first.
+    pub fn years_until(&self, later: &Date) -> Option<u16> {
+        let birthday_passed = (later.months, later.days) >= (self.months, self.days);
+        later
+            .years
+            .checked_sub(self.years)?
+            .checked_sub(u16::from(!birthday_passed))
+    }
+
+    /// Days since 1970-01-01 in the proleptic Gregorian calendar, handy for spans and week buckets.
+    pub fn days_since_epoch(&self) -> i64 {
+        // Howard Hinnant's days_from_civil
+        let y = i64::from(self.years) - i64::from(self.months <= 2);
+        let m = i64::from(self.months);
+        let d = i64::from(self.days);
+        let era = y.div_euclid(400);
+        let yoe = y - era * 400;
+        let doy = (153 * (m + if m > 2 { -3 } else { 9 }) + 2) / 5 + d - 1;
+        let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+        era * 146097 + doe - 719468
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Time {
     hours: u8,
     minutes: u8,
@@ -41,11 +97,13 @@ pub struct Time {
 }
 
 impl Time {
+    /// Accepts `hh:mm:ss` and, as some firmware writes it, `hh:mm` (seconds 0).
+    /// `None` for out-of-range components such as "25:00:00".
     pub fn from_string(time_hms: &str) -> Option<Time> {
         let mut iterator = time_hms.trim_matches('"').split(':');
         let h = iterator.next()?;
         let m = iterator.next()?;
-        let s = iterator.next()?;
+        let s = iterator.next();
 
         if iterator.next().is_some() {
             return None;
@@ -53,59 +111,234 @@ impl Time {
 
         let hours = h.parse::<u8>().ok()?;
         let minutes = m.parse::<u8>().ok()?;
-        let seconds = s.parse::<u8>().ok()?;
+        let seconds = match s {
+            Some(s) => s.parse::<u8>().ok()?,
+            None => 0,
+        };
 
-        return Some(Time {
+        if hours > 23 || minutes > 59 || seconds > 59 {
+            return None;
+        }
+
+        Some(Time {
             hours,
             minutes,
             seconds,
-        });
+        })
     }
 
+    #[deprecated(note = "use `to_string`, which writes a zero-padded `hh:mm:ss`")]
     pub fn to_srting(&self) -> String {
-        format!("{}:{}:{}", self.hours, self.minutes, self.seconds)
+        self.to_string()
+    }
+
+    pub fn hours(&self) -> u8 {
+        self.hours
+    }
+
+    pub fn minutes(&self) -> u8 {
+        self.minutes
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct DateTime {
     date: Date,
     time: Time,
 }
 
 impl DateTime {
+    pub fn date(&self) -> &Date {
+        &self.date
+    }
+
+    pub fn time(&self) -> &Time {
+        &self.time
+    }
+
+    pub fn seconds_since_epoch(&self) -> i64 {
+        self.date.days_since_epoch() * 86_400
+            + i64::from(self.time.hours) * 3_600
+            + i64::from(self.time.minutes) * 60
+            + i64::from(self.time.seconds)
+    }
+
+    /// `yyyy-mm-ddThh:mm:ss`, e.g. `2020-01-05T09:07:00`, for exports read by other programs.
+    pub fn format_iso(&self) -> String {
+        format!("{}T{}", self.date.format_iso(), self.time)
+    }
+
     pub fn from_string(date_dmy: &str, time_hms: &str) -> Option<DateTime> {
-        match (Date::from_string(date_dmy), Time::from_string(time_hms)) {
-            (Some(date), Some(time)) => Some(DateTime { date, time }),
-            options => {
-                println!("Datetime is unable to parse this shit: {:?}", options);
-                return None;
-            }
-        }
+        Some(DateTime {
+            date: Date::from_string(date_dmy)?,
+            time: Time::from_string(time_hms)?,
+        })
+    }
+}
+
+/// `dd/mm/yyyy`, zero-padded, as the device writes it.
+impl fmt::Display for Date {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:02}/{:02}/{:04}", self.days, self.months, self.years)
+    }
+}
+
+/// `hh:mm:ss`, zero-padded.
+impl fmt::Display for Time {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:02}:{:02}:{:02}",
+            self.hours, self.minutes, self.seconds
+        )
+    }
+}
+
+impl fmt::Display for DateTime {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}", self.date, self.time)
+    }
+}
+
+/// A percentage as read from the scale. Construction never fails: readings
+/// outside `0..=100` (or not finite) are kept as-is and flagged, so a corrupt
+/// segmental read shows up instead of being silently clamped away.
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Percent(f32);
+
+impl Percent {
+    pub fn new(value: f32) -> Percent {
+        Percent(value)
     }
 
-    pub fn to_string(&self) -> String {
-        format!("{} {}", self.date.to_srting(), self.time.to_srting())
+    pub fn value(&self) -> f32 {
+        self.0
+    }
+
+    /// `true` for values no body composition percentage can take.
+    pub fn is_suspicious(&self) -> bool {
+        !(0.0..=100.0).contains(&self.0)
     }
 }
 
-#[derive(Debug, Clone)]
+impl From<f32> for Percent {
+    fn from(value: f32) -> Self {
+        Percent::new(value)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum Gender {
     Male,
     Female,
     Other(u8),
 }
 
-impl Gender {
-    pub fn to_string(&self) -> String {
+impl fmt::Display for Gender {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
-            Gender::Male => "Boy".to_string(),
-            Gender::Female => "Girl".to_string(),
-            Gender::Other(n) => format!("Unknown gender: {}", n),
+            Gender::Male => write!(f, "Boy"),
+            Gender::Female => write!(f, "Girl"),
+            Gender::Other(n) => write!(f, "Unknown gender: {}", n),
         }
     }
 }
 
+/// Accepts "male"/"m"/"boy", "female"/"f"/"girl" (any case), a device code,
+/// or the `Display` output for unknown codes.
+impl FromStr for Gender {
+    type Err = ParseEnumError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let normalized = s.trim().to_ascii_lowercase();
+        let code = normalized
+            .strip_prefix("unknown gender:")
+            .unwrap_or(&normalized)
+            .trim();
+        match code {
+            "male" | "m" | "boy" => Ok(Gender::Male),
+            "female" | "f" | "girl" => Ok(Gender::Female),
+            _ => code
+                .parse::<u8>()
+                .map(Gender::from)
+                .map_err(|_| ParseEnumError::new("gender", s)),
+        }
+    }
+}
+
+/// Scale model from the `MO` field.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum DeviceModel {
+    Bc601,
+    Bc603,
+    Bc545,
+    /// Any other `MO` value, as written; empty when the field was missing.
+    Unknown(String),
+}
+
+impl Default for DeviceModel {
+    fn default() -> Self {
+        DeviceModel::Unknown(String::new())
+    }
+}
+
+/// Matches on the model number, so "BC-601", "BC601" and "BC-601FS" are all `Bc601`.
+impl From<&str> for DeviceModel {
+    fn from(model: &str) -> Self {
+        let model = model.trim().trim_matches('"');
+        if model.contains("601") {
+            DeviceModel::Bc601
+        } else if model.contains("603") {
+            DeviceModel::Bc603
+        } else if model.contains("545") {
+            DeviceModel::Bc545
+        } else {
+            DeviceModel::Unknown(model.to_string())
+        }
+    }
+}
+
+impl fmt::Display for DeviceModel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DeviceModel::Bc601 => write!(f, "BC-601"),
+            DeviceModel::Bc603 => write!(f, "BC-603"),
+            DeviceModel::Bc545 => write!(f, "BC-545"),
+            DeviceModel::Unknown(model) if model.is_empty() => write!(f, "unknown"),
+            DeviceModel::Unknown(model) => write!(f, "{}", model),
+        }
+    }
+}
+
+/// Returned by the `FromStr` impls of the device code enums, `Units` and
+/// `BmiCategory`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseEnumError {
+    kind: &'static str,
+    input: String,
+}
+
+impl ParseEnumError {
+    pub fn new(kind: &'static str, input: &str) -> ParseEnumError {
+        ParseEnumError {
+            kind,
+            input: input.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for ParseEnumError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "unknown {}: {:?}", self.kind, self.input)
+    }
+}
+
+impl Error for ParseEnumError {}
+
 impl From<u8> for Gender {
     fn from(code: u8) -> Self {
         match code {
@@ -115,3 +348,191 @@ impl From<u8> for Gender {
         }
     }
 }
+
+/// `AL`, the activity level picked on the scale.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ActivityLevel {
+    Low,
+    Medium,
+    High,
+    Other(u8),
+}
+
+impl From<u8> for ActivityLevel {
+    fn from(code: u8) -> Self {
+        match code {
+            1 => ActivityLevel::Low,
+            2 => ActivityLevel::Medium,
+            3 => ActivityLevel::High,
+            _ => ActivityLevel::Other(code),
+        }
+    }
+}
+
+impl fmt::Display for ActivityLevel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ActivityLevel::Low => write!(f, "Low"),
+            ActivityLevel::Medium => write!(f, "Medium"),
+            ActivityLevel::High => write!(f, "High"),
+            ActivityLevel::Other(n) => write!(f, "Unknown activity level: {}", n),
+        }
+    }
+}
+
+/// Accepts "low"/"medium"/"high" (any case), a device code, or the `Display`
+/// output for unknown codes.
+impl FromStr for ActivityLevel {
+    type Err = ParseEnumError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let normalized = s.trim().to_ascii_lowercase();
+        let code = normalized
+            .strip_prefix("unknown activity level:")
+            .unwrap_or(&normalized)
+            .trim();
+        match code {
+            "low" => Ok(ActivityLevel::Low),
+            "medium" => Ok(ActivityLevel::Medium),
+            "high" => Ok(ActivityLevel::High),
+            _ => code
+                .parse::<u8>()
+                .map(ActivityLevel::from)
+                .map_err(|_| ParseEnumError::new("activity level", s)),
+        }
+    }
+}
+
+/// `Bt`, standard or athlete mode.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BodyType {
+    Standard,
+    Athlete,
+    Other(u8),
+}
+
+impl From<u8> for BodyType {
+    fn from(code: u8) -> Self {
+        match code {
+            0 => BodyType::Standard,
+            2 => BodyType::Athlete,
+            _ => BodyType::Other(code),
+        }
+    }
+}
+
+impl fmt::Display for BodyType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BodyType::Standard => write!(f, "Standard"),
+            BodyType::Athlete => write!(f, "Athlete"),
+            BodyType::Other(n) => write!(f, "Unknown body type: {}", n),
+        }
+    }
+}
+
+/// Accepts "standard"/"athlete" (any case), a device code, or the `Display`
+/// output for unknown codes.
+impl FromStr for BodyType {
+    type Err = ParseEnumError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let normalized = s.trim().to_ascii_lowercase();
+        let code = normalized
+            .strip_prefix("unknown body type:")
+            .unwrap_or(&normalized)
+            .trim();
+        match code {
+            "standard" => Ok(BodyType::Standard),
+            "athlete" => Ok(BodyType::Athlete),
+            _ => code
+                .parse::<u8>()
+                .map(BodyType::from)
+                .map_err(|_| ParseEnumError::new("body type", s)),
+        }
+    }
+}
+
+const _: () = {
+    fn assert_send_sync<T: Send + Sync>() {}
+    let _ = assert_send_sync::<Date>;
+    let _ = assert_send_sync::<Time>;
+    let _ = assert_send_sync::<DateTime>;
+    let _ = assert_send_sync::<Gender>;
+    let _ = assert_send_sync::<Percent>;
+};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn round_trips<T>(values: &[T])
+    where
+        T: fmt::Display + FromStr<Err = ParseEnumError> + PartialEq + fmt::Debug,
+    {
+        for value in values {
+            assert_eq!(value.to_string().parse::<T>().as_ref(), Ok(value));
+        }
+    }
+
+    #[test]
+    fn device_code_enums_round_trip_through_display() {
+        round_trips(&[Gender::Male, Gender::Female, Gender::Other(7)]);
+        round_trips(&[
+            ActivityLevel::Low,
+            ActivityLevel::Medium,
+            ActivityLevel::High,
+            ActivityLevel::Other(9),
+        ]);
+        round_trips(&[BodyType::Standard, BodyType::Athlete, BodyType::Other(1)]);
+    }
+
+    #[test]
+    fn device_code_enums_parse_raw_codes() {
+        assert_eq!("2".parse(), Ok(ActivityLevel::Medium));
+        assert_eq!("2".parse(), Ok(BodyType::Athlete));
+        assert_eq!(" ATHLETE ".parse(), Ok(BodyType::Athlete));
+        assert!("sometimes".parse::<ActivityLevel>().is_err());
+
+        assert_eq!("m".parse(), Ok(Gender::Male));
+        assert_eq!("F".parse(), Ok(Gender::Female));
+        assert_eq!("1".parse(), Ok(Gender::Male));
+        assert_eq!("2".parse(), Ok(Gender::Female));
+        assert_eq!("7".parse(), Ok(Gender::Other(7)));
+        assert!("robot".parse::<Gender>().is_err());
+    }
+
+    #[test]
+    fn dates_compare_year_first() {
+        let new_year = Date::from_string("01/01/2020").unwrap();
+        let new_years_eve = Date::from_string("31/12/2019").unwrap();
+        assert!(new_year > new_years_eve);
+
+        let morning = DateTime::from_string("01/01/2020", "00:00:01").unwrap();
+        let night = DateTime::from_string("31/12/2019", "23:59:59").unwrap();
+        assert!(morning > night);
+        assert_eq!(night.cmp(&night.clone()), std::cmp::Ordering::Equal);
+    }
+
+    #[test]
+    fn time_reads_with_or_without_seconds() {
+        assert_eq!(
+            Time::from_string("\"09:07\"").unwrap().to_string(),
+            "09:07:00"
+        );
+        assert_eq!(
+            Time::from_string("09:07:30").unwrap().to_string(),
+            "09:07:30"
+        );
+        assert_eq!(Time::from_string("09"), None);
+        assert_eq!(Time::from_string("09:07:30:01"), None);
+    }
+
+    #[test]
+    fn time_rejects_out_of_range_components() {
+        assert_eq!(Time::from_string("25:00:00"), None);
+        assert_eq!(Time::from_string("12:60:00"), None);
+        assert_eq!(Time::from_string("12:00:60"), None);
+        assert!(Time::from_string("23:59:59").is_some());
+    }
+}
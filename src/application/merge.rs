@@ -0,0 +1,84 @@
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use super::{Measurement, UserMeasurements};
+
+/// How close two measurements must be to count as the same weigh-in when
+/// users are merged, e.g. copies of one reading from overlapping backups.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct MergeTolerance {
+    pub seconds: i64,
+    pub weight_kg: f32,
+}
+
+impl Default for MergeTolerance {
+    fn default() -> Self {
+        MergeTolerance {
+            seconds: 60,
+            weight_kg: 0.1,
+        }
+    }
+}
+
+impl UserMeasurements {
+    /// Sorts the measurements, then drops every dated one within `tolerance`
+    /// of the previous one kept, so the earliest copy survives. Undated rows
+    /// are never dropped. Returns how many were removed.
+    pub fn dedupe_near_duplicates(&mut self, tolerance: &MergeTolerance) -> usize {
+        self.measurements.sort();
+        let before = self.measurements.len();
+        let mut kept: Vec<Measurement> = Vec::with_capacity(before);
+        for m in self.measurements.drain(..) {
+            let duplicate =
+                kept.last()
+                    .is_some_and(|previous| match (&previous.date_time, &m.date_time) {
+                        (Some(previous_at), Some(at)) => {
+                            (at.seconds_since_epoch() - previous_at.seconds_since_epoch()).abs()
+                                <= tolerance.seconds
+                                && (m.weight_kg - previous.weight_kg).abs() <= tolerance.weight_kg
+                        }
+                        _ => false,
+                    });
+            if !duplicate {
+                kept.push(m);
+            }
+        }
+        self.measurements = kept;
+        before - self.measurements.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::application::tests::user_at;
+
+    #[test]
+    fn a_copy_one_second_later_is_dropped() {
+        let mut user = user_at(
+            0,
+            &[
+                ("01/01/2020", "08:00:01", 80.05),
+                ("01/01/2020", "08:00:00", 80.0),
+            ],
+        );
+        assert_eq!(user.dedupe_near_duplicates(&MergeTolerance::default()), 1);
+        assert_eq!(user.measurements.len(), 1);
+        assert_eq!(user.measurements[0].weight_kg, 80.0);
+    }
+
+    #[test]
+    fn a_reading_61_seconds_later_is_kept() {
+        let mut user = user_at(
+            0,
+            &[
+                ("01/01/2020", "08:00:00", 80.0),
+                ("01/01/2020", "08:01:01", 80.0),
+            ],
+        );
+        assert_eq!(user.dedupe_near_duplicates(&MergeTolerance::default()), 0);
+        assert_eq!(user.measurements.len(), 2);
+    }
+}
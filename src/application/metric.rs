@@ -0,0 +1,78 @@
+use std::fmt;
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use super::Measurement;
+use super::general_data_structs::Percent;
+
+/// A numeric series that can be followed over time.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum Metric {
+    #[default]
+    WeightKg,
+    Bmi,
+    FatPercent,
+    MusclePercent,
+    BoneKg,
+    WaterPercent,
+    VisceralFatRating,
+    /// Fat % over muscle %, see `Measurement::fat_muscle_ratio`.
+    FatMuscleRatio,
+}
+
+impl Metric {
+    pub const ALL: [Metric; 8] = [
+        Metric::WeightKg,
+        Metric::Bmi,
+        Metric::FatPercent,
+        Metric::MusclePercent,
+        Metric::BoneKg,
+        Metric::WaterPercent,
+        Metric::VisceralFatRating,
+        Metric::FatMuscleRatio,
+    ];
+
+    /// `None` when the row doesn't carry this metric (older firmware) or the
+    /// percentage read is out of range, so corrupt reads stay out of the stats.
+    pub fn value(&self, m: &Measurement) -> Option<f32> {
+        match self {
+            Metric::WeightKg => Some(m.weight_kg),
+            Metric::Bmi => Some(m.bmi),
+            Metric::FatPercent => Self::plausible(Some(m.fat_percent)),
+            Metric::MusclePercent => Self::plausible(m.muscle_percent),
+            Metric::BoneKg => m.bone_kg,
+            Metric::WaterPercent => Self::plausible(m.water_percent),
+            Metric::VisceralFatRating => m.visceral_fat_rating.map(f32::from),
+            Metric::FatMuscleRatio => m.fat_muscle_ratio(),
+        }
+    }
+
+    fn plausible(percent: Option<Percent>) -> Option<f32> {
+        percent.filter(|p| !p.is_suspicious()).map(|p| p.value())
+    }
+
+    pub fn unit(&self) -> &'static str {
+        match self {
+            Metric::WeightKg | Metric::BoneKg => "kg",
+            Metric::FatPercent | Metric::MusclePercent | Metric::WaterPercent => "%",
+            Metric::Bmi | Metric::VisceralFatRating | Metric::FatMuscleRatio => "",
+        }
+    }
+}
+
+impl fmt::Display for Metric {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Metric::WeightKg => write!(f, "Weight"),
+            Metric::Bmi => write!(f, "BMI"),
+            Metric::FatPercent => write!(f, "Fat"),
+            Metric::MusclePercent => write!(f, "Muscle"),
+            Metric::BoneKg => write!(f, "Bones"),
+            Metric::WaterPercent => write!(f, "Water"),
+            Metric::VisceralFatRating => write!(f, "Visceral fat"),
+            Metric::FatMuscleRatio => write!(f, "Fat/muscle ratio"),
+        }
+    }
+}
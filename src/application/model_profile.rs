@@ -0,0 +1,51 @@
+use super::field_tag::FieldTag;
+use super::parser::ParserConfig;
+
+/// Which tag set a DATA row is read with, picked per row from its `MO` value.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ModelProfile {
+    /// The base tag set; also used for unknown models.
+    Bc601,
+    /// Adds the advanced tags (phase angle, impedance) on top of the BC-601 set.
+    Bc603,
+}
+
+/// Where the value of one DATA tag goes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ModelField {
+    Tag(FieldTag),
+    PhaseAngle,
+    Impedance,
+}
+
+impl ModelProfile {
+    /// Picks the profile for one row. BC-603 FS firmware often writes "BC-601"
+    /// as its model, so a row carrying any advanced tag counts as BC-603 too.
+    pub fn detect(entries: &[&str], config: &ParserConfig) -> ModelProfile {
+        let mut pairs = entries.chunks_exact(2);
+        let is_bc603 = pairs.any(|pair| {
+            let (key, value) = (pair[0], pair[1]);
+            (FieldTag::from_code(key) == Some(FieldTag::Model) && value.contains("603"))
+                || key == config.phase_angle_tag
+                || key == config.impedance_tag
+        });
+        if is_bc603 {
+            ModelProfile::Bc603
+        } else {
+            ModelProfile::Bc601
+        }
+    }
+
+    /// `None` for tags this model doesn't define; those end up in `extras`.
+    pub fn resolve(&self, key: &str, config: &ParserConfig) -> Option<ModelField> {
+        if let Some(tag) = FieldTag::from_code(key) {
+            return Some(ModelField::Tag(tag));
+        }
+        match self {
+            ModelProfile::Bc601 => None,
+            ModelProfile::Bc603 if key == config.phase_angle_tag => Some(ModelField::PhaseAngle),
+            ModelProfile::Bc603 if key == config.impedance_tag => Some(ModelField::Impedance),
+            ModelProfile::Bc603 => None,
+        }
+    }
+}
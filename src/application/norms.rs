@@ -0,0 +1,142 @@
+use std::{fmt, str::FromStr};
+
+use super::general_data_structs::ParseEnumError;
+#[cfg(feature = "gui")]
+use super::general_data_structs::{DateTime, Gender};
+#[cfg(feature = "gui")]
+use super::metric::Metric;
+#[cfg(feature = "gui")]
+use super::{UserMeasurements, ViewFilter};
+
+/// WHO adult BMI range, the same for everyone.
+const HEALTHY_BMI: (f32, f32) = (18.5, 25.0);
+/// WHO lower bound of obesity.
+const OBESE_BMI: f32 = 30.0;
+/// Healthy body fat % by age (Gallagher et al., 2000, the ranges printed in
+/// Tanita's manuals): each entry applies from its age up to the next one.
+#[cfg(feature = "gui")]
+const HEALTHY_FAT_MALE: [(u16, f32, f32); 3] =
+    [(20, 8.0, 20.0), (40, 11.0, 22.0), (60, 13.0, 25.0)];
+#[cfg(feature = "gui")]
+const HEALTHY_FAT_FEMALE: [(u16, f32, f32); 3] =
+    [(20, 21.0, 33.0), (40, 23.0, 34.0), (60, 24.0, 36.0)];
+/// The published ranges are for adults up to this age.
+#[cfg(feature = "gui")]
+const NORM_AGES: std::ops::RangeInclusive<u16> = 20..=79;
+
+/// Healthy `(low, high)` of `metric` at `age`. `None` for metrics without a
+/// norm, ages outside `NORM_AGES` and, for body fat, an unknown gender.
+#[cfg(feature = "gui")]
+pub fn healthy_range(metric: Metric, gender: &Gender, age: u16) -> Option<(f32, f32)> {
+    if !NORM_AGES.contains(&age) {
+        return None;
+    }
+    match metric {
+        Metric::Bmi => Some(HEALTHY_BMI),
+        Metric::FatPercent => {
+            let table = match gender {
+                Gender::Male => &HEALTHY_FAT_MALE,
+                Gender::Female => &HEALTHY_FAT_FEMALE,
+                Gender::Other(_) => return None,
+            };
+            table
+                .iter()
+                .rev()
+                .find(|(from, ..)| age >= *from)
+                .map(|&(_, low, high)| (low, high))
+        }
+        _ => None,
+    }
+}
+
+/// WHO adult BMI class.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BmiCategory {
+    Underweight,
+    Healthy,
+    Overweight,
+    Obese,
+}
+
+impl BmiCategory {
+    pub fn from_bmi(bmi: f32) -> BmiCategory {
+        match bmi {
+            bmi if bmi < HEALTHY_BMI.0 => BmiCategory::Underweight,
+            bmi if bmi < HEALTHY_BMI.1 => BmiCategory::Healthy,
+            bmi if bmi < OBESE_BMI => BmiCategory::Overweight,
+            _ => BmiCategory::Obese,
+        }
+    }
+}
+
+impl fmt::Display for BmiCategory {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BmiCategory::Underweight => write!(f, "Underweight"),
+            BmiCategory::Healthy => write!(f, "Healthy"),
+            BmiCategory::Overweight => write!(f, "Overweight"),
+            BmiCategory::Obese => write!(f, "Obese"),
+        }
+    }
+}
+
+/// Accepts the `Display` names in any case; "normal" is taken as `Healthy`.
+impl FromStr for BmiCategory {
+    type Err = ParseEnumError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "underweight" => Ok(BmiCategory::Underweight),
+            "healthy" | "normal" => Ok(BmiCategory::Healthy),
+            "overweight" => Ok(BmiCategory::Overweight),
+            "obese" => Ok(BmiCategory::Obese),
+            _ => Err(ParseEnumError::new("BMI category", s)),
+        }
+    }
+}
+
+#[cfg(feature = "gui")]
+impl UserMeasurements {
+    /// Healthy range of `metric` at each point of `series`, using the profile's
+    /// gender and the age at that date. Points without a norm are left out.
+    pub(super) fn healthy_band(
+        &self,
+        metric: Metric,
+        filter: ViewFilter,
+    ) -> Vec<(DateTime, f32, f32)> {
+        self.charted(filter)
+            .into_iter()
+            .filter_map(|m| {
+                let age = m.effective_age(&self.profile.birth_date_dmy, true)?;
+                let (low, high) = healthy_range(metric, &self.profile.gender, age)?;
+                Some((m.date_time.clone()?, low, high))
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bmi_category_round_trips_through_display() {
+        for category in [
+            BmiCategory::Underweight,
+            BmiCategory::Healthy,
+            BmiCategory::Overweight,
+            BmiCategory::Obese,
+        ] {
+            assert_eq!(category.to_string().parse(), Ok(category));
+        }
+        assert!("chunky".parse::<BmiCategory>().is_err());
+    }
+
+    #[test]
+    fn bmi_category_uses_who_cutoffs() {
+        assert_eq!(BmiCategory::from_bmi(18.4), BmiCategory::Underweight);
+        assert_eq!(BmiCategory::from_bmi(18.5), BmiCategory::Healthy);
+        assert_eq!(BmiCategory::from_bmi(25.0), BmiCategory::Overweight);
+        assert_eq!(BmiCategory::from_bmi(30.0), BmiCategory::Obese);
+    }
+}
@@ -0,0 +1,74 @@
+use std::fmt;
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// How numbers are written on screen. Exports always use `Plain`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum NumberFormat {
+    /// `1234.5`
+    #[default]
+    Plain,
+    /// `1,234.5`
+    Grouped,
+    /// `1 234,5`
+    Continental,
+}
+
+impl NumberFormat {
+    pub const ALL: [NumberFormat; 3] = [
+        NumberFormat::Plain,
+        NumberFormat::Grouped,
+        NumberFormat::Continental,
+    ];
+
+    fn separators(&self) -> (Option<char>, char) {
+        match self {
+            NumberFormat::Plain => (None, '.'),
+            NumberFormat::Grouped => (Some(','), '.'),
+            NumberFormat::Continental => (Some(' '), ','),
+        }
+    }
+
+    pub fn format<T: ToString>(&self, value: T) -> String {
+        let plain = value.to_string();
+        let (group_sep, decimal_sep) = self.separators();
+
+        let (sign, unsigned) = match plain.strip_prefix('-') {
+            Some(rest) => ("-", rest),
+            None => ("", plain.as_str()),
+        };
+        let (int_part, frac_part) = match unsigned.split_once('.') {
+            Some((i, f)) => (i, Some(f)),
+            None => (unsigned, None),
+        };
+
+        let mut out = String::with_capacity(plain.len() + int_part.len() / 3);
+        out.push_str(sign);
+        for (i, c) in int_part.chars().enumerate() {
+            if let Some(sep) = group_sep
+                && i > 0
+                && (int_part.len() - i) % 3 == 0
+            {
+                out.push(sep);
+            }
+            out.push(c);
+        }
+        if let Some(frac) = frac_part {
+            out.push(decimal_sep);
+            out.push_str(frac);
+        }
+        out
+    }
+}
+
+impl fmt::Display for NumberFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            NumberFormat::Plain => write!(f, "1234.5"),
+            NumberFormat::Grouped => write!(f, "1,234.5"),
+            NumberFormat::Continental => write!(f, "1 234,5"),
+        }
+    }
+}
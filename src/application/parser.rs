@@ -1,24 +1,86 @@
 use std::{
     collections::{BTreeMap, BTreeSet},
     error::Error,
-    fmt, fs,
+    fmt,
+    fs::{self, File},
+    io::{BufRead, BufReader, Lines},
     path::{Path, PathBuf},
+    str::FromStr,
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+        mpsc::Sender,
+    },
 };
 
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use super::field_tag::FieldTag;
+use super::general_data_structs::{Date, DateTime};
+use super::model_profile::{ModelField, ModelProfile};
+
 const PROFILE_FOLDER_NAME: &str = "SYSTEM";
 const DATA_FOLDER_NAME: &str = "DATA";
 const DATA_FILE_NAME_PREFIX: &str = "DATA";
 const PROFILE_FILE_NAME_PREFIX: &str = "PROF";
 const CSV_EXTENTION_NAME: &str = ".CSV";
+/// DATA lines between two `ParseProgress` reports.
+const PROGRESS_EVERY_LINES: usize = 256;
 
 #[derive(Debug)]
 pub enum TanitaValidationError {
-    MissingDir(&'static str),
+    /// `name` is not inside the `picked` folder.
+    MissingDir {
+        name: &'static str,
+        picked: PathBuf,
+    },
     NoFilesFound,
+    /// `TanitaParser::cancel` was set before the parse finished.
+    Cancelled,
     Unpaired {
         missing_in_data: BTreeSet<u32>,
         missing_in_profile: BTreeSet<u32>,
     },
+    UnreadableFile(PathBuf),
+    UnreadableDir(PathBuf),
+    /// A DATA row whose `DT`/`Ti` fields don't form a valid timestamp (1-based line).
+    InvalidTimestamp {
+        file: PathBuf,
+        line: usize,
+    },
+    /// Named like a DATA/PROF file but the number can't be read (`DATA01A.CSV`),
+    /// so the file is ignored.
+    MalformedIndex(PathBuf),
+    /// Several files map to one user number once case is ignored
+    /// (`data1.csv` next to `DATA1.CSV`); only `used` is read.
+    IndexCollision {
+        used: PathBuf,
+        ignored: Vec<PathBuf>,
+    },
+    /// A DATA row with values that aren't numbers (1-based line).
+    UnreadableValues {
+        file: PathBuf,
+        line: usize,
+        warnings: Vec<ParseWarning>,
+    },
+    /// A DATA row whose `CS` doesn't match its contents, or that has none (1-based line).
+    ChecksumMismatch {
+        file: PathBuf,
+        line: usize,
+    },
+    /// A DATA row naming the same key more than once (1-based line).
+    DuplicateKeys {
+        file: PathBuf,
+        line: usize,
+        keys: Vec<String>,
+    },
+    /// A DATA row that isn't a list of key/value pairs (1-based line).
+    MalformedRow {
+        file: PathBuf,
+        line: usize,
+        error: RowParseError,
+    },
 }
 
 impl fmt::Display for TanitaValidationError {
@@ -37,8 +99,74 @@ impl fmt::Display for TanitaValidationError {
                     missing_in_data, missing_in_profile
                 )
             }
-            TanitaValidationError::MissingDir(name) => {
-                write!(f, "Missing required dir: {}", name)
+            TanitaValidationError::Cancelled => write!(f, "Parsing was cancelled"),
+            TanitaValidationError::MissingDir { name, picked } => {
+                write!(
+                    f,
+                    "Picked {} but no {} folder inside it — did you select the right folder?",
+                    picked.display(),
+                    name
+                )
+            }
+            TanitaValidationError::UnreadableFile(path) => {
+                write!(f, "Unable to read file: {}", path.display())
+            }
+            TanitaValidationError::UnreadableDir(path) => {
+                write!(f, "Unable to read folder: {}", path.display())
+            }
+            TanitaValidationError::ChecksumMismatch { file, line } => {
+                write!(
+                    f,
+                    "{} line {}: checksum doesn't match, the row may be corrupt",
+                    file.display(),
+                    line
+                )
+            }
+            TanitaValidationError::InvalidTimestamp { file, line } => {
+                write!(f, "Invalid date/time in {} line {}", file.display(), line)
+            }
+            TanitaValidationError::DuplicateKeys { file, line, keys } => {
+                write!(
+                    f,
+                    "{} line {}: repeated key(s) {}, the first value was used",
+                    file.display(),
+                    line,
+                    keys.join(", ")
+                )
+            }
+            TanitaValidationError::UnreadableValues {
+                file,
+                line,
+                warnings,
+            } => {
+                let warnings: Vec<String> = warnings.iter().map(|w| w.to_string()).collect();
+                write!(
+                    f,
+                    "{} line {}: read as 0: {}",
+                    file.display(),
+                    line,
+                    warnings.join("; ")
+                )
+            }
+            TanitaValidationError::MalformedRow { file, line, error } => {
+                write!(f, "{} line {}: {}", file.display(), line, error)
+            }
+            TanitaValidationError::IndexCollision { used, ignored } => {
+                let ignored: Vec<String> =
+                    ignored.iter().map(|p| p.display().to_string()).collect();
+                write!(
+                    f,
+                    "Ignored {}: same user number as {}, which was used",
+                    ignored.join(", "),
+                    used.display()
+                )
+            }
+            TanitaValidationError::MalformedIndex(path) => {
+                write!(
+                    f,
+                    "Ignored {}: its user number can't be read",
+                    path.display()
+                )
             }
         }
     }
@@ -46,29 +174,361 @@ impl fmt::Display for TanitaValidationError {
 
 impl Error for TanitaValidationError {}
 
+/// A value that didn't read as a number, so the field fell back to 0.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct ParseWarning {
+    pub key: String,
+    pub raw_value: String,
+    pub reason: String,
+}
+
+impl fmt::Display for ParseWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}={:?}: {}", self.key, self.raw_value, self.reason)
+    }
+}
+
+/// Something noticed while reading a card that didn't stop the parse. Kept on
+/// `ParsedDataset` for the caller to show, as a window has no console to print to.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DatasetWarning {
+    /// User index, as in `RawUserRecord::index`.
+    pub index: usize,
+    /// 1-based line in the user's DATA file; 0 when not about a DATA row.
+    pub line: usize,
+    pub kind: WarningKind,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum WarningKind {
+    /// A key no field is read from, e.g. a model's extra tag. Reported once
+    /// per parse, at the first row carrying it.
+    UnknownKey {
+        file: &'static str,
+        key: String,
+        value: String,
+    },
+    /// A PROF value that didn't read as a number and was taken as 0.
+    UnreadableProfileValue(ParseWarning),
+    /// `DT`/`Ti` don't form a timestamp, so the row is left out of the measurements.
+    InvalidTimestamp { date: String, time: String },
+    /// The DATA file has no rows.
+    EmptyDataFile,
+    /// The profile has no DATA file at all, kept as
+    /// `ParserConfig::allow_profiles_without_data` is set.
+    MissingDataFile,
+    /// The selected PROF revision doesn't read as a profile (no valid birth
+    /// date), so the user is left out.
+    UnreadableProfile,
+    /// PROF `Hm` looked like meters and was multiplied up to centimeters.
+    HeightInMeters { height: f32, height_cm: f32 },
+    /// A DATA row names `key` more than once; its first value was kept.
+    DuplicateKey { key: String },
+}
+
+impl fmt::Display for DatasetWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "User {}", self.index + 1)?;
+        if self.line > 0 {
+            write!(f, " line {}", self.line)?;
+        }
+        match &self.kind {
+            WarningKind::UnknownKey { file, key, value } => {
+                write!(f, ": unknown {} key {:?} with value {:?}", file, key, value)
+            }
+            WarningKind::UnreadableProfileValue(warning) => {
+                write!(f, ": PROF value read as 0: {}", warning)
+            }
+            WarningKind::InvalidTimestamp { date, time } => {
+                write!(f, ": invalid date/time {:?} {:?}, row left out", date, time)
+            }
+            WarningKind::EmptyDataFile => write!(f, ": DATA file is empty"),
+            WarningKind::MissingDataFile => write!(f, ": no DATA file"),
+            WarningKind::UnreadableProfile => write!(f, ": profile can't be read, user left out"),
+            WarningKind::HeightInMeters { height, height_cm } => {
+                write!(
+                    f,
+                    ": height {} read as meters, using {} cm",
+                    height, height_cm
+                )
+            }
+            WarningKind::DuplicateKey { key } => {
+                write!(f, ": repeated key {:?}, the first value was used", key)
+            }
+        }
+    }
+}
+
+/// Structural problem with a single DATA row, from `DataRaw::try_from_csv_row`.
+#[derive(Debug, Clone)]
+pub enum RowParseError {
+    /// Nothing but whitespace.
+    Empty,
+    /// The last key has no value. `partial` holds everything read before it.
+    OddFieldCount {
+        fields: usize,
+        partial: Box<DataRaw>,
+    },
+}
+
+impl RowParseError {
+    /// What could still be read from the row.
+    pub fn partial(&self) -> Option<&DataRaw> {
+        match self {
+            RowParseError::Empty => None,
+            RowParseError::OddFieldCount { partial, .. } => Some(partial),
+        }
+    }
+}
+
+impl fmt::Display for RowParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RowParseError::Empty => write!(f, "empty row"),
+            RowParseError::OddFieldCount { fields, .. } => {
+                write!(f, "{} fields, the last key has no value", fields)
+            }
+        }
+    }
+}
+
+impl Error for RowParseError {}
+
 pub type TanitaResult<T> = Result<T, TanitaValidationError>;
 
-#[derive(Debug)]
+/// Every folder under `root` (itself included) holding both DATA and SYSTEM,
+/// sorted. Matched folders are not searched further and symlinks are not
+/// followed; unreadable folders are skipped.
+pub fn find_tanita_folders(root: &Path) -> Vec<PathBuf> {
+    let mut found = Vec::new();
+    let mut pending = vec![root.to_path_buf()];
+    while let Some(dir) = pending.pop() {
+        if dir.join(DATA_FOLDER_NAME).is_dir() && dir.join(PROFILE_FOLDER_NAME).is_dir() {
+            found.push(dir);
+            continue;
+        }
+        let Ok(entries) = fs::read_dir(&dir) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            if entry.file_type().is_ok_and(|t| t.is_dir()) {
+                pending.push(entry.path());
+            }
+        }
+    }
+    found.sort();
+    found
+}
+
+/// Where the scale puts its folder on the card, below the mount root.
+const CARD_FOLDER_PATH: [&str; 2] = ["TANITA", "GRAPHV1"];
+
+/// The Tanita folder of a card mounted at `mount`: `mount` itself, or
+/// TANITA/GRAPHV1 or GRAPHV1 right below it (any case). Only those few
+/// folders are looked at, so it is cheap enough to poll.
+pub fn find_card_folder(mount: &Path) -> Option<PathBuf> {
+    let is_tanita_folder =
+        |dir: &Path| dir.join(DATA_FOLDER_NAME).is_dir() && dir.join(PROFILE_FOLDER_NAME).is_dir();
+    let graph = CARD_FOLDER_PATH[1];
+    [
+        Some(mount.to_path_buf()),
+        child_dir(mount, CARD_FOLDER_PATH[0]).and_then(|tanita| child_dir(&tanita, graph)),
+        child_dir(mount, graph),
+    ]
+    .into_iter()
+    .flatten()
+    .find(|dir| is_tanita_folder(dir))
+}
+
+/// The folder in `dir` named `name`, ignoring case as FAT cards may show
+/// names in either.
+fn child_dir(dir: &Path, name: &str) -> Option<PathBuf> {
+    fs::read_dir(dir)
+        .ok()?
+        .flatten()
+        .find(|entry| {
+            entry.file_type().is_ok_and(|t| t.is_dir())
+                && entry
+                    .file_name()
+                    .to_str()
+                    .is_some_and(|n| n.eq_ignore_ascii_case(name))
+        })
+        .map(|entry| entry.path())
+}
+
+/// Everything a full parse produces.
+#[derive(Debug, Clone, Default)]
+pub struct ParsedDataset {
+    pub users: Vec<RawUserRecord>,
+    /// DATA/PROF-looking files that were ignored, see `TanitaValidationError::MalformedIndex`.
+    pub skipped_files: Vec<PathBuf>,
+    /// Files sharing a user number, as (file used, files ignored), see
+    /// `TanitaValidationError::IndexCollision`.
+    pub index_collisions: Vec<(PathBuf, Vec<PathBuf>)>,
+    /// User numbers with a PROF but no DATA file, left out unless
+    /// `ParserConfig::allow_profiles_without_data`.
+    pub missing_in_data: BTreeSet<u32>,
+    /// User numbers with a DATA but no PROF file, always left out.
+    pub missing_in_profile: BTreeSet<u32>,
+    /// In the order they were found.
+    pub warnings: Vec<DatasetWarning>,
+}
+
+impl ParsedDataset {
+    /// `TanitaValidationError::Unpaired` for the files that were left out, if any.
+    pub fn unpaired(&self) -> Option<TanitaValidationError> {
+        (!self.missing_in_data.is_empty() || !self.missing_in_profile.is_empty()).then(|| {
+            TanitaValidationError::Unpaired {
+                missing_in_data: self.missing_in_data.clone(),
+                missing_in_profile: self.missing_in_profile.clone(),
+            }
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
 pub struct RawUserRecord {
     pub index: usize,
-    pub profile: ProfRaw,
+    /// One entry per non-empty line of PROF{N}.CSV, oldest first; a profile that
+    /// was edited on the device can carry several revisions.
+    pub profiles: Vec<ProfRaw>,
     pub data: Vec<DataRaw>,
+    /// DATA{N}.CSV exists but has no rows: the profile was set up, nothing was measured yet.
+    pub data_file_empty: bool,
+}
+
+/// Knobs for how DATA/PROF rows are interpreted.
+#[derive(Debug, Clone)]
+pub struct ParserConfig {
+    /// Tag carrying phase angle (degrees) on models that report it (BC-603 and up),
+    /// see `ModelProfile`.
+    /// Not confirmed on every firmware, override it if your device uses other letters.
+    pub phase_angle_tag: String,
+    /// Tag carrying whole-body impedance (ohms) on models that report it.
+    pub impedance_tag: String,
+    /// When set, only DATA rows dated within this inclusive range are parsed.
+    pub date_filter: Option<(Date, Date)>,
+    /// Treat a PROF{N} without DATA{N} as a user with no measurements (the device
+    /// creates the profile first) instead of an unpaired file.
+    pub allow_profiles_without_data: bool,
+    /// Copy every DATA line into `DataRaw::raw_row`. Off by default: it roughly
+    /// doubles the memory a parsed dataset takes, which adds up over years of
+    /// daily weigh-ins on several users.
+    pub keep_raw: bool,
 }
 
+impl Default for ParserConfig {
+    fn default() -> Self {
+        ParserConfig {
+            phase_angle_tag: "PA".to_string(),
+            impedance_tag: "IM".to_string(),
+            date_filter: None,
+            allow_profiles_without_data: true,
+            keep_raw: false,
+        }
+    }
+}
+
+/// Holds only owned paths, so it is `Send + Sync` and can be cloned into
+/// worker threads or async tasks.
+#[derive(Debug, Clone)]
 pub struct TanitaParser {
     pub root_dir: PathBuf,
+    /// The DATA or SYSTEM folder that was picked, when `root_dir` is its
+    /// parent rather than the folder given to `new`.
+    pub picked_subfolder: Option<PathBuf>,
+    pub config: ParserConfig,
+    /// Set from another thread to stop a running parse; checked before each row.
+    pub cancel: Arc<AtomicBool>,
+    /// Receives a `ParseProgress` every few hundred DATA lines and after each
+    /// user, when set. A dropped receiver doesn't stop the parse.
+    pub progress: Option<Sender<ParseProgress>>,
+}
+
+/// How far `TanitaParser::get_raw_users_records` got.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ParseProgress {
+    /// Position of the user being read among the `users` pairs, from 0.
+    pub user: usize,
+    pub users: usize,
+    /// DATA lines of that user read so far.
+    pub lines: usize,
 }
 
 impl TanitaParser {
-    fn parse_u8(s: &str) -> u8 {
-        s.parse::<u8>().unwrap_or(0)
+    /// `picked` may also be the DATA or SYSTEM folder itself, a common
+    /// mis-pick; its parent is used then and `picked` is kept in
+    /// `picked_subfolder`. See `resolve_root`.
+    pub fn new(picked: PathBuf) -> TanitaParser {
+        let root_dir = Self::resolve_root(picked.clone());
+        TanitaParser {
+            picked_subfolder: (root_dir != picked).then_some(picked),
+            root_dir,
+            config: ParserConfig::default(),
+            cancel: Arc::new(AtomicBool::new(false)),
+            progress: None,
+        }
+    }
+
+    /// The folder holding DATA and SYSTEM: `picked` itself, or its parent when
+    /// `picked` is named DATA or SYSTEM (any case).
+    pub fn resolve_root(picked: PathBuf) -> PathBuf {
+        let is_subfolder = picked
+            .file_name()
+            .and_then(|name| name.to_str())
+            .is_some_and(|name| {
+                name.eq_ignore_ascii_case(DATA_FOLDER_NAME)
+                    || name.eq_ignore_ascii_case(PROFILE_FOLDER_NAME)
+            });
+        match picked.parent() {
+            Some(parent) if is_subfolder => parent.to_path_buf(),
+            _ => picked,
+        }
+    }
+
+    /// `s` as a number, or 0 with a note in `warnings` when it doesn't read as one.
+    fn parse_number<T>(key: &str, s: &str, warnings: &mut Vec<ParseWarning>) -> T
+    where
+        T: FromStr + Default,
+        T::Err: fmt::Display,
+    {
+        s.parse::<T>().unwrap_or_else(|err| {
+            warnings.push(ParseWarning {
+                key: key.to_string(),
+                raw_value: s.to_string(),
+                reason: err.to_string(),
+            });
+            T::default()
+        })
+    }
+
+    /// `lines()` only drops a `\r` that precedes `\n`, so a file with mixed or
+    /// old-Mac endings can leave one on the last field and corrupt `CS`.
+    fn strip_cr(row: &str) -> &str {
+        row.trim_end_matches('\r')
     }
-    fn parse_u16(s: &str) -> u16 {
-        s.parse::<u16>().unwrap_or(0)
+
+    /// `CS` is the XOR of every byte of the row before `,CS,` (the comma is not
+    /// included), as two hex digits.
+    /// `false` when it doesn't match or the row has no `CS` at all, as when a
+    /// write was cut short by a dying battery.
+    pub fn verify_checksum(row: &str) -> bool {
+        let row = Self::strip_cr(row);
+        let Some(at) = row.rfind(",CS,") else {
+            return false;
+        };
+        let Ok(expected) = u8::from_str_radix(&Self::unquote(&row[at + 4..]), 16) else {
+            return false;
+        };
+        row.as_bytes()[..at].iter().fold(0, |acc, byte| acc ^ byte) == expected
     }
-    fn parse_f32(s: &str) -> f32 {
-        s.parse::<f32>().unwrap_or(0.0)
+
+    /// `None` for an empty value (`Fr,,`), which would otherwise read as a real 0.
+    fn parse_present_f32(key: &str, s: &str, warnings: &mut Vec<ParseWarning>) -> Option<f32> {
+        (!s.trim().is_empty()).then(|| Self::parse_number(key, s, warnings))
     }
+
     fn unquote(s: &str) -> String {
         let t = s.trim();
         t.strip_prefix('"')
@@ -77,73 +537,444 @@ impl TanitaParser {
             .to_string()
     }
 
-    pub fn get_raw_users_records(&self) -> Vec<RawUserRecord> {
-        let data_folder = self.require_dir(&self.root_dir, DATA_FOLDER_NAME).unwrap();
-        let system_folder = self
-            .require_dir(&self.root_dir, PROFILE_FOLDER_NAME)
-            .unwrap();
-        let data_files = self.collect_files(&data_folder).unwrap();
-        let prof_files = self.collect_files(&system_folder).unwrap();
-        let mut tanita_pairs: Vec<TanitaPair> = Vec::with_capacity(prof_files.len());
-
-        for (file_num, profile_file) in prof_files {
-            if data_files[&file_num].exists() {
-                tanita_pairs.push(TanitaPair {
-                    index: file_num - 1,
-                    profile: profile_file,
-                    //TODO: why do i need to clone this one but not profile?
-                    data: data_files[&file_num].clone(),
-                });
-            } else {
-                panic!("profile and data do not match");
-            }
+    /// Fails only when the folders or files can't be read at all. Profiles
+    /// without data and data without a profile are left out and listed in
+    /// `ParsedDataset::unpaired`, so one stray file doesn't hide everyone else.
+    pub fn get_raw_users_records(&self) -> TanitaResult<ParsedDataset> {
+        let data_folder = self.require_dir(&self.root_dir, DATA_FOLDER_NAME)?;
+        let system_folder = self.require_dir(&self.root_dir, PROFILE_FOLDER_NAME)?;
+        let data_files = self.collect_files(&data_folder)?;
+        let prof_files = self.collect_files(&system_folder)?;
+        if data_files.is_empty() && prof_files.is_empty() {
+            return Err(TanitaValidationError::NoFilesFound);
         }
+        let mut skipped_files = self.malformed_files(&data_folder);
+        skipped_files.extend(self.malformed_files(&system_folder));
+        let mut index_collisions = self.colliding_files(&data_folder);
+        index_collisions.extend(self.colliding_files(&system_folder));
+        let missing_in_profile: BTreeSet<u32> = data_files
+            .keys()
+            .filter(|idx| !prof_files.contains_key(idx))
+            .map(|idx| *idx as u32)
+            .collect();
+        let (tanita_pairs, missing_in_data) = self.pair_files(prof_files, &data_files);
 
         let mut users_records = Vec::with_capacity(tanita_pairs.len());
+        let mut seen_unknown_keys = BTreeSet::new();
+        let mut warnings = Vec::new();
 
         //Now we need to read all those files and parse data in it;
-        for pair in tanita_pairs {
-            let prof_file_content = pair.get_profile_file_content();
-            let data_file_content = pair.get_data_file_content();
-            let first_profile_line = prof_file_content.lines().collect::<Vec<&str>>()[0];
+        let users = tanita_pairs.len();
+        for (user, pair) in tanita_pairs.into_iter().enumerate() {
+            let report = |lines| {
+                if let Some(progress) = &self.progress {
+                    let _ = progress.send(ParseProgress { user, users, lines });
+                }
+            };
+            if self.cancel.load(Ordering::Relaxed) {
+                return Err(TanitaValidationError::Cancelled);
+            }
+            let prof_file_content = pair.get_profile_file_content()?;
 
             let mut raw_user_record = RawUserRecord {
                 index: pair.index,
                 data: Vec::new(),
-                profile: ProfRaw::from_csv_row(first_profile_line),
+                profiles: Self::profile_revisions(&prof_file_content),
+                data_file_empty: true,
             };
+            for profile in &raw_user_record.profiles {
+                Self::unknown_key_warnings(
+                    "PROF",
+                    (pair.index, 0),
+                    &profile.extras,
+                    &mut seen_unknown_keys,
+                    &mut warnings,
+                );
+                warnings.extend(profile.parse_warnings.iter().map(|warning| DatasetWarning {
+                    index: pair.index,
+                    line: 0,
+                    kind: WarningKind::UnreadableProfileValue(warning.clone()),
+                }));
+                let height_cm = super::height_in_cm(profile.height_cm);
+                if height_cm != profile.height_cm {
+                    warnings.push(DatasetWarning {
+                        index: pair.index,
+                        line: 0,
+                        kind: WarningKind::HeightInMeters {
+                            height: profile.height_cm,
+                            height_cm,
+                        },
+                    });
+                }
+            }
 
-            for data in data_file_content.lines() {
-                raw_user_record.data.push(DataRaw::from_csv_row(data));
+            // Each row is parsed as it is read.
+            let mut lines = 0;
+            for (line, data) in pair.data_file_lines()?.into_iter().flatten().enumerate() {
+                if self.cancel.load(Ordering::Relaxed) {
+                    return Err(TanitaValidationError::Cancelled);
+                }
+                lines = line + 1;
+                if lines % PROGRESS_EVERY_LINES == 0 {
+                    report(lines);
+                }
+                let data = data.map_err(|_| {
+                    TanitaValidationError::UnreadableFile(pair.data.clone().unwrap_or_default())
+                })?;
+                let data = data.as_str();
+                if !data.trim().is_empty() {
+                    raw_user_record.data_file_empty = false;
+                }
+                if !self.row_in_date_filter(data) {
+                    continue;
+                }
+                let mut data_raw = DataRaw::from_csv_row(data, &self.config);
+                Self::unknown_key_warnings(
+                    "DATA",
+                    (pair.index, line + 1),
+                    &data_raw.extras,
+                    &mut seen_unknown_keys,
+                    &mut warnings,
+                );
+                warnings.extend(data_raw.duplicate_keys.iter().map(|key| DatasetWarning {
+                    index: pair.index,
+                    line: line + 1,
+                    kind: WarningKind::DuplicateKey { key: key.clone() },
+                }));
+                if data_raw.has_invalid_timestamp() {
+                    warnings.push(DatasetWarning {
+                        index: pair.index,
+                        line: line + 1,
+                        kind: WarningKind::InvalidTimestamp {
+                            date: data_raw.date_dmy.clone(),
+                            time: data_raw.time_hms.clone(),
+                        },
+                    });
+                }
+                data_raw.source_line = line + 1;
+                if self.config.keep_raw {
+                    data_raw.raw_row = Some(data.to_string());
+                }
+                raw_user_record.data.push(data_raw);
+            }
+            report(lines);
+            if raw_user_record.data_file_empty {
+                warnings.push(DatasetWarning {
+                    index: pair.index,
+                    line: 0,
+                    kind: if pair.data.is_some() {
+                        WarningKind::EmptyDataFile
+                    } else {
+                        WarningKind::MissingDataFile
+                    },
+                });
             }
             users_records.push(raw_user_record);
         }
-        users_records
+        Ok(ParsedDataset {
+            users: users_records,
+            skipped_files,
+            index_collisions,
+            missing_in_data,
+            missing_in_profile,
+            warnings,
+        })
+    }
+
+    /// Warns about each unknown key the first time it shows up in a parse run;
+    /// a model with extra tags would otherwise repeat the same warning for every row.
+    fn unknown_key_warnings(
+        file: &'static str,
+        (index, line): (usize, usize),
+        extras: &[(String, String)],
+        seen: &mut BTreeSet<String>,
+        warnings: &mut Vec<DatasetWarning>,
+    ) {
+        for (key, value) in extras {
+            if seen.insert(format!("{}/{}", file, key)) {
+                warnings.push(DatasetWarning {
+                    index,
+                    line,
+                    kind: WarningKind::UnknownKey {
+                        file,
+                        key: key.clone(),
+                        value: value.clone(),
+                    },
+                });
+            }
+        }
+    }
+
+    /// The PROF/DATA file pairs `get_raw_users_records` would read, by user
+    /// number, without reading them. Unpaired files are left out.
+    /// Fails with the first missing DATA/SYSTEM folder, before any file is read.
+    pub fn pairs(&self) -> TanitaResult<Vec<TanitaPair>> {
+        let data_files =
+            self.collect_files(&self.require_dir(&self.root_dir, DATA_FOLDER_NAME)?)?;
+        let prof_files =
+            self.collect_files(&self.require_dir(&self.root_dir, PROFILE_FOLDER_NAME)?)?;
+        Ok(self.pair_files(prof_files, &data_files).0)
+    }
+
+    /// Each profile with its DATA file, and the user numbers left out for
+    /// having none (unless `ParserConfig::allow_profiles_without_data`).
+    fn pair_files(
+        &self,
+        prof_files: BTreeMap<usize, PathBuf>,
+        data_files: &BTreeMap<usize, PathBuf>,
+    ) -> (Vec<TanitaPair>, BTreeSet<u32>) {
+        let mut missing_in_data = BTreeSet::new();
+        let mut pairs = Vec::with_capacity(prof_files.len());
+        for (file_num, profile_file) in prof_files {
+            let data = data_files.get(&file_num).cloned();
+            if data.is_none() && !self.config.allow_profiles_without_data {
+                missing_in_data.insert(file_num as u32);
+                continue;
+            }
+            pairs.push(TanitaPair {
+                index: file_num - 1,
+                profile: profile_file,
+                data,
+            });
+        }
+        (pairs, missing_in_data)
+    }
+
+    pub fn check_root(&self) -> TanitaResult<()> {
+        self.require_dir(&self.root_dir, DATA_FOLDER_NAME)?;
+        self.require_dir(&self.root_dir, PROFILE_FOLDER_NAME)?;
+        Ok(())
+    }
+
+    fn profile_revisions(content: &str) -> Vec<ProfRaw> {
+        content
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(ProfRaw::from_csv_row)
+            .collect()
+    }
+
+    /// Reads only the PROF files, for a roster without the cost of a full parse.
+    /// Each user comes with the latest revision of their profile.
+    /// Indices match `RawUserRecord::index`.
+    pub fn get_profiles(&self) -> TanitaResult<Vec<(usize, ProfRaw)>> {
+        let system_folder = self.require_dir(&self.root_dir, PROFILE_FOLDER_NAME)?;
+        let prof_files = self.collect_files(&system_folder)?;
+        if prof_files.is_empty() {
+            return Err(TanitaValidationError::NoFilesFound);
+        }
+
+        prof_files
+            .into_iter()
+            .map(|(file_num, profile_file)| {
+                let content = fs::read_to_string(&profile_file)
+                    .map_err(|_| TanitaValidationError::UnreadableFile(profile_file))?;
+                let latest = Self::profile_revisions(&content).pop().unwrap_or_default();
+                Ok((file_num - 1, latest))
+            })
+            .collect()
+    }
+
+    /// Runs the structural checks (dirs present, files paired, timestamps
+    /// parseable) without building any records. Empty means the folder is clean.
+    pub fn validate(&self) -> Vec<TanitaValidationError> {
+        let mut errors = Vec::new();
+        let data_folder = self.require_dir(&self.root_dir, DATA_FOLDER_NAME);
+        let system_folder = self.require_dir(&self.root_dir, PROFILE_FOLDER_NAME);
+        let (data_folder, system_folder) = match (data_folder, system_folder) {
+            (Ok(data), Ok(system)) => (data, system),
+            (data, system) => {
+                errors.extend(data.err());
+                errors.extend(system.err());
+                return errors;
+            }
+        };
+
+        let (data_files, prof_files) = match (
+            self.collect_files(&data_folder),
+            self.collect_files(&system_folder),
+        ) {
+            (Ok(data), Ok(prof)) if !data.is_empty() || !prof.is_empty() => (data, prof),
+            _ => {
+                errors.push(TanitaValidationError::NoFilesFound);
+                return errors;
+            }
+        };
+        errors.extend(
+            self.malformed_files(&data_folder)
+                .into_iter()
+                .chain(self.malformed_files(&system_folder))
+                .map(TanitaValidationError::MalformedIndex),
+        );
+        errors.extend(
+            self.colliding_files(&data_folder)
+                .into_iter()
+                .chain(self.colliding_files(&system_folder))
+                .map(|(used, ignored)| TanitaValidationError::IndexCollision { used, ignored }),
+        );
+
+        let missing_in_data: BTreeSet<u32> = prof_files
+            .keys()
+            .filter(|idx| !self.config.allow_profiles_without_data && !data_files.contains_key(idx))
+            .map(|idx| *idx as u32)
+            .collect();
+        let missing_in_profile: BTreeSet<u32> = data_files
+            .keys()
+            .filter(|idx| !prof_files.contains_key(idx))
+            .map(|idx| *idx as u32)
+            .collect();
+        if !missing_in_data.is_empty() || !missing_in_profile.is_empty() {
+            errors.push(TanitaValidationError::Unpaired {
+                missing_in_data,
+                missing_in_profile,
+            });
+        }
+
+        for data_file in data_files.values() {
+            let Ok(content) = fs::read_to_string(data_file) else {
+                errors.push(TanitaValidationError::UnreadableFile(data_file.clone()));
+                continue;
+            };
+            for (line, row) in content.lines().enumerate() {
+                let data_raw = match DataRaw::try_from_csv_row(row, &self.config) {
+                    Ok(data_raw) => data_raw,
+                    Err(error) => {
+                        let partial = error.partial().cloned();
+                        errors.push(TanitaValidationError::MalformedRow {
+                            file: data_file.clone(),
+                            line: line + 1,
+                            error,
+                        });
+                        let Some(partial) = partial else {
+                            continue;
+                        };
+                        partial
+                    }
+                };
+                if !data_raw.duplicate_keys.is_empty() {
+                    errors.push(TanitaValidationError::DuplicateKeys {
+                        file: data_file.clone(),
+                        line: line + 1,
+                        keys: data_raw.duplicate_keys.clone(),
+                    });
+                }
+                if !data_raw.checksum_ok {
+                    errors.push(TanitaValidationError::ChecksumMismatch {
+                        file: data_file.clone(),
+                        line: line + 1,
+                    });
+                }
+                if !data_raw.parse_warnings.is_empty() {
+                    errors.push(TanitaValidationError::UnreadableValues {
+                        file: data_file.clone(),
+                        line: line + 1,
+                        warnings: data_raw.parse_warnings.clone(),
+                    });
+                }
+                if data_raw.has_invalid_timestamp() {
+                    errors.push(TanitaValidationError::InvalidTimestamp {
+                        file: data_file.clone(),
+                        line: line + 1,
+                    });
+                }
+            }
+        }
+
+        errors
+    }
+
+    /// Cheap pre-check that only looks at the `DT` field, so rows outside
+    /// `config.date_filter` are skipped before the full parse.
+    /// Rows without a readable date are kept and left to the full parse.
+    fn row_in_date_filter(&self, row: &str) -> bool {
+        let Some((from, to)) = &self.config.date_filter else {
+            return true;
+        };
+
+        let mut entries = row.split(',');
+        while let (Some(key), Some(value)) = (entries.next(), entries.next()) {
+            if key == FieldTag::Date.code() {
+                return match Date::from_string(&Self::unquote(value)) {
+                    Some(date) => *from <= date && date <= *to,
+                    None => true,
+                };
+            }
+        }
+        true
     }
+
     fn require_dir(&self, p: &Path, name: &'static str) -> TanitaResult<PathBuf> {
         let dir = p.join(name);
         if dir.is_dir() {
             Ok(dir)
         } else {
-            Err(TanitaValidationError::MissingDir(name))
+            Err(TanitaValidationError::MissingDir {
+                name,
+                picked: p.to_path_buf(),
+            })
         }
     }
 
     fn get_index(&self, file_name: &str) -> Option<usize> {
+        self.index_digits(file_name)?.parse().ok()
+    }
+
+    /// What sits between the DATA/PROF prefix and the extension, if the name has both.
+    fn index_digits(&self, file_name: &str) -> Option<String> {
         let name = file_name.to_ascii_uppercase();
         let name_wihtout_extention = name.strip_suffix(CSV_EXTENTION_NAME)?;
-        let digits = if let Some(d) = name_wihtout_extention.strip_prefix(DATA_FILE_NAME_PREFIX) {
-            d
-        } else if let Some(d) = name_wihtout_extention.strip_prefix(PROFILE_FILE_NAME_PREFIX) {
-            d
-        } else {
-            return None;
+        let digits = name_wihtout_extention
+            .strip_prefix(DATA_FILE_NAME_PREFIX)
+            .or_else(|| name_wihtout_extention.strip_prefix(PROFILE_FILE_NAME_PREFIX))?;
+        Some(digits.to_string())
+    }
+
+    /// Files in `dir` that `collect_files` passes over because their number doesn't parse.
+    fn malformed_files(&self, dir: &Path) -> Vec<PathBuf> {
+        let Ok(read_result) = fs::read_dir(dir) else {
+            return Vec::new();
         };
-        digits.parse().ok()
+        let mut malformed: Vec<PathBuf> = read_result
+            .flatten()
+            .filter(|entry| {
+                entry.file_name().to_str().is_some_and(|file_name| {
+                    self.index_digits(file_name)
+                        .is_some_and(|digits| digits.parse::<usize>().is_err())
+                })
+            })
+            .map(|entry| entry.path())
+            .collect();
+        malformed.sort();
+        malformed
     }
 
+    /// One file per user number. When names collide the first in byte order is
+    /// used, so the result doesn't depend on directory order and the device's
+    /// upper-case name wins over a lower-case copy.
     fn collect_files(&self, dir: &Path) -> TanitaResult<BTreeMap<usize, PathBuf>> {
-        let mut collecton = BTreeMap::new();
+        Ok(self
+            .indexed_files(dir)?
+            .into_iter()
+            .filter_map(|(idx, files)| Some((idx, files.into_iter().next()?)))
+            .collect())
+    }
+
+    /// Groups that `collect_files` had to pick from, as (file used, files ignored).
+    fn colliding_files(&self, dir: &Path) -> Vec<(PathBuf, Vec<PathBuf>)> {
+        self.indexed_files(dir)
+            .unwrap_or_default()
+            .into_values()
+            .filter(|files| files.len() > 1)
+            .map(|mut files| {
+                let used = files.remove(0);
+                (used, files)
+            })
+            .collect()
+    }
+
+    /// DATA/PROF files in `dir` by user number, each list sorted by path.
+    fn indexed_files(&self, dir: &Path) -> TanitaResult<BTreeMap<usize, Vec<PathBuf>>> {
+        let mut collecton: BTreeMap<usize, Vec<PathBuf>> = BTreeMap::new();
         let read = fs::read_dir(dir);
         match read {
             Ok(read_result) => {
@@ -151,33 +982,66 @@ impl TanitaParser {
                     if let Some(file_name) = entry.file_name().to_str()
                         && let Some(idx) = self.get_index(file_name)
                     {
-                        collecton.insert(idx, entry.path());
+                        collecton.entry(idx).or_default().push(entry.path());
                     }
                 }
             }
-            Err(err) => {
-                eprintln!("Collect file error: {}", err);
-                return Err(TanitaValidationError::NoFilesFound);
-            }
+            Err(_) => return Err(TanitaValidationError::UnreadableDir(dir.to_path_buf())),
+        }
+        for files in collecton.values_mut() {
+            files.sort();
         }
         Ok(collecton)
     }
 }
 
+/// PROF{N}.CSV and DATA{N}.CSV of one user, see `TanitaParser::pairs`.
 #[derive(Debug, Clone)]
 pub struct TanitaPair {
     index: usize,
     profile: PathBuf,
-    data: PathBuf,
+    /// `None` for a profile that was created but never measured.
+    data: Option<PathBuf>,
 }
 
 impl TanitaPair {
-    pub fn get_profile_file_content(&self) -> String {
-        fs::read_to_string(self.profile.clone()).unwrap()
+    /// Pair index, N - 1.
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    pub fn profile_file(&self) -> &Path {
+        &self.profile
+    }
+
+    pub fn data_file(&self) -> Option<&Path> {
+        self.data.as_deref()
     }
 
-    pub fn get_data_file_content(&self) -> String {
-        fs::read_to_string(self.data.clone()).unwrap()
+    pub fn get_profile_file_content(&self) -> TanitaResult<String> {
+        fs::read_to_string(&self.profile)
+            .map_err(|_| TanitaValidationError::UnreadableFile(self.profile.clone()))
+    }
+
+    /// The DATA file read line by line, so a file with thousands of rows
+    /// isn't held in memory whole. `None` when the pair has no DATA file.
+    pub fn data_file_lines(&self) -> TanitaResult<Option<Lines<BufReader<File>>>> {
+        match &self.data {
+            Some(data) => File::open(data)
+                .map(|file| Some(BufReader::new(file).lines()))
+                .map_err(|_| TanitaValidationError::UnreadableFile(data.clone())),
+            None => Ok(None),
+        }
+    }
+
+    /// The whole DATA file as one string, empty when the pair has none. Fine
+    /// for small files; `get_raw_users_records` streams with `data_file_lines`.
+    pub fn get_data_file_content(&self) -> TanitaResult<String> {
+        match &self.data {
+            Some(data) => fs::read_to_string(data)
+                .map_err(|_| TanitaValidationError::UnreadableFile(data.clone())),
+            None => Ok(String::new()),
+        }
     }
 }
 
@@ -199,30 +1063,49 @@ pub struct ProfRaw {
     pub activity_level_code: u8,
     /// `CS` — checksum / record code reported at the end (often hex-like).
     pub checksum: String,
+    /// Unrecognized key/value pairs, in row order.
+    pub extras: Vec<(String, String)>,
+    /// Values that didn't read as numbers and were taken as 0.
+    pub parse_warnings: Vec<ParseWarning>,
 }
 
 impl ProfRaw {
+    /// A stray `\r` at the end of `row` is ignored, see `TanitaParser::strip_cr`.
     pub fn from_csv_row(row: &str) -> ProfRaw {
-        let data_entries: Vec<&str> = row.split(',').collect();
+        let data_entries: Vec<&str> = TanitaParser::strip_cr(row).split(',').collect();
         let mut profile_raw = ProfRaw::default();
 
         let mut key_pointer = 0;
-        while key_pointer < data_entries.len() {
+        while key_pointer + 1 < data_entries.len() {
             let key = data_entries[key_pointer];
             let value = data_entries[key_pointer + 1];
 
-            match key {
-                "MO" => profile_raw.model = TanitaParser::unquote(value),
-                "DB" => profile_raw.birth_date_dmy = TanitaParser::unquote(value),
-                "Bt" => profile_raw.body_type_code = TanitaParser::parse_u8(value),
-                "GE" => profile_raw.gender_code = TanitaParser::parse_u8(value),
-                "Hm" => profile_raw.height_cm = TanitaParser::parse_f32(value),
-                "AL" => profile_raw.activity_level_code = TanitaParser::parse_u8(value),
-                "CS" => profile_raw.checksum = TanitaParser::unquote(value),
-
-                _ => {
-                    println!("[Profile] Some extra key: {:?} and value: {:?}", key, value);
+            match FieldTag::from_code(key) {
+                Some(FieldTag::Model) => profile_raw.model = TanitaParser::unquote(value),
+                Some(FieldTag::BirthDate) => {
+                    profile_raw.birth_date_dmy = TanitaParser::unquote(value)
+                }
+                Some(FieldTag::BodyType) => {
+                    profile_raw.body_type_code =
+                        TanitaParser::parse_number(key, value, &mut profile_raw.parse_warnings)
+                }
+                Some(FieldTag::Gender) => {
+                    profile_raw.gender_code =
+                        TanitaParser::parse_number(key, value, &mut profile_raw.parse_warnings)
                 }
+                Some(FieldTag::Height) => {
+                    profile_raw.height_cm =
+                        TanitaParser::parse_number(key, value, &mut profile_raw.parse_warnings)
+                }
+                Some(FieldTag::ActivityLevel) => {
+                    profile_raw.activity_level_code =
+                        TanitaParser::parse_number(key, value, &mut profile_raw.parse_warnings)
+                }
+                Some(FieldTag::Checksum) => profile_raw.checksum = TanitaParser::unquote(value),
+
+                _ => profile_raw
+                    .extras
+                    .push((key.to_string(), value.to_string())),
             }
             key_pointer += 2;
         }
@@ -261,17 +1144,17 @@ pub struct DataRaw {
     /// `FW` Global fat (%).
     pub fat_percent: f32,
 
-    // --- Segmental fat (%) ---
+    // --- Segmental fat (%), `None` when the key is there with an empty value ---
     /// `Fr` Arm fat (right) %.
-    pub fat_right_arm_pct: f32,
+    pub fat_right_arm_pct: Option<f32>,
     /// `Fl` Arm fat (left) %.
-    pub fat_left_arm_pct: f32,
+    pub fat_left_arm_pct: Option<f32>,
     /// `FR` Leg fat (right) %.
-    pub fat_right_leg_pct: f32,
+    pub fat_right_leg_pct: Option<f32>,
     /// `FL` Leg fat (left) %.
-    pub fat_left_leg_pct: f32,
+    pub fat_left_leg_pct: Option<f32>,
     /// `FT` Torso fat %.
-    pub fat_trunk_pct: f32,
+    pub fat_trunk_pct: Option<f32>,
 
     // --- Muscle (%), whole + segments (present on newer rows) ---
     /// `mW` Global muscle %.
@@ -299,62 +1182,268 @@ pub struct DataRaw {
     /// `rD` Daily calorie intake (DCI, kcal).
     pub daily_calorie_intake_kcal: Option<u16>,
 
+    // --- Bioimpedance (higher models only, tags set in `ParserConfig`) ---
+    /// Phase angle (degrees).
+    pub phase_angle: Option<f32>,
+    /// Whole-body impedance (ohms).
+    pub impedance_ohms: Option<f32>,
+
     // --- Trailer ---
     /// `CS` Frame/check code (changes per entry; keep as-is).
     pub checksum: String,
+    /// `checksum` matches the row, see `TanitaParser::verify_checksum`. A row
+    /// that fails is kept but suspect: its numbers may be garbage.
+    pub checksum_ok: bool,
 
     // --- Catch-all for future tags (lossless) ---
     pub extras: Vec<(String, String)>,
+
+    /// Keys the row repeats; only the first value of each was kept.
+    pub duplicate_keys: Vec<String>,
+    /// Values that didn't read as numbers and were taken as 0.
+    pub parse_warnings: Vec<ParseWarning>,
+
+    /// 1-based line of DATA{N}.CSV this row was read from (0 if not read from a file).
+    pub source_line: usize,
+    /// The untouched DATA line, only kept with `ParserConfig::keep_raw`.
+    pub raw_row: Option<String>,
+}
+
+/// Value of `key` in `extras`, quotes removed; the first one if the row repeats it.
+pub(crate) fn find_extra<'a>(extras: &'a [(String, String)], key: &str) -> Option<&'a str> {
+    extras
+        .iter()
+        .find(|(k, _)| k == key)
+        .map(|(_, value)| value.trim().trim_matches('"'))
 }
 
 impl DataRaw {
-    pub fn from_csv_row(row: &str) -> DataRaw {
-        let data_entries: Vec<&str> = row.split(',').collect();
-        let mut data_raw = DataRaw::default();
+    /// Value of a tag the parser has no field for, quotes removed; the first
+    /// one if the row repeats it.
+    ///
+    /// ```
+    /// use tanita::parser::{DataRaw, ParserConfig};
+    ///
+    /// let row = r#"MO,"BC-601",DT,"05/01/2020",Ti,"09:07:00",Wk,80.0,Zx,"12.5",CS,02"#;
+    /// let data = DataRaw::from_csv_row(row, &ParserConfig::default());
+    /// assert_eq!(data.extra("Zx"), Some("12.5"));
+    /// assert_eq!(data.extra("Wk"), None);
+    /// ```
+    pub fn extra(&self, key: &str) -> Option<&str> {
+        find_extra(&self.extras, key)
+    }
 
+    /// `DT`/`Ti` don't form a timestamp. The placeholder date written before
+    /// the clock was set doesn't count: such rows are kept, undated.
+    pub fn has_invalid_timestamp(&self) -> bool {
+        !Date::is_unset_placeholder(&self.date_dmy)
+            && DateTime::from_string(&self.date_dmy, &self.time_hms).is_none()
+    }
+
+    /// Like `from_csv_row`, but says when the row isn't a list of key/value
+    /// pairs instead of quietly reading what it can.
+    pub fn try_from_csv_row(row: &str, config: &ParserConfig) -> Result<DataRaw, RowParseError> {
+        let row = TanitaParser::strip_cr(row);
+        if row.trim().is_empty() {
+            return Err(RowParseError::Empty);
+        }
+        let data_raw = DataRaw::from_csv_row(row, config);
+        let fields = row.split(',').count();
+        if fields % 2 == 1 {
+            return Err(RowParseError::OddFieldCount {
+                fields,
+                partial: Box::new(data_raw),
+            });
+        }
+        Ok(data_raw)
+    }
+
+    /// Never fails: unreadable values become 0 (see `parse_warnings`), a trailing key without a value
+    /// is dropped and a repeated key keeps its first value (see
+    /// `duplicate_keys`). A stray `\r` at the end of `row` is ignored, see
+    /// `TanitaParser::strip_cr`.
+    pub fn from_csv_row(row: &str, config: &ParserConfig) -> DataRaw {
+        let data_entries: Vec<&str> = TanitaParser::strip_cr(row).split(',').collect();
+        let mut data_raw = DataRaw {
+            checksum_ok: TanitaParser::verify_checksum(row),
+            ..DataRaw::default()
+        };
+        let model_profile = ModelProfile::detect(&data_entries, config);
+
+        let mut seen_keys = BTreeSet::new();
         let mut key_pointer = 0;
-        while key_pointer < data_entries.len() {
+        while key_pointer + 1 < data_entries.len() {
             let key = data_entries[key_pointer];
             let value = data_entries[key_pointer + 1];
+            if !seen_keys.insert(key) {
+                data_raw.duplicate_keys.push(key.to_string());
+                key_pointer += 2;
+                continue;
+            }
 
-            match key {
-                "MO" => data_raw.model = TanitaParser::unquote(value),
-                "DT" => data_raw.date_dmy = TanitaParser::unquote(value),
-                "Ti" => data_raw.time_hms = TanitaParser::unquote(value),
-                "GE" => data_raw.gender_code = TanitaParser::parse_u8(value),
-                "AG" => data_raw.age_years = TanitaParser::parse_u8(value),
-                "Hm" => data_raw.height_cm = TanitaParser::parse_f32(value),
-
-                "AL" => data_raw.activity_level_code = TanitaParser::parse_u8(value),
-                "Bt" => data_raw.body_type_code = TanitaParser::parse_u8(value),
-                "Wk" => data_raw.weight_kg = TanitaParser::parse_f32(value),
-                "MI" => data_raw.bmi = TanitaParser::parse_f32(value),
-
-                "FW" => data_raw.fat_percent = TanitaParser::parse_f32(value),
-                "Fr" => data_raw.fat_right_arm_pct = TanitaParser::parse_f32(value),
-                "Fl" => data_raw.fat_left_arm_pct = TanitaParser::parse_f32(value),
-                "FR" => data_raw.fat_right_leg_pct = TanitaParser::parse_f32(value),
-                "FL" => data_raw.fat_left_leg_pct = TanitaParser::parse_f32(value),
-                "FT" => data_raw.fat_trunk_pct = TanitaParser::parse_f32(value),
-
-                "mW" => data_raw.muscle_percent = Some(TanitaParser::parse_f32(value)),
-                "ml" => data_raw.muscle_left_arm_pct = Some(TanitaParser::parse_f32(value)),
-                "mr" => data_raw.muscle_right_arm_pct = Some(TanitaParser::parse_f32(value)),
-                "mR" => data_raw.muscle_right_leg_pct = Some(TanitaParser::parse_f32(value)),
-                "mL" => data_raw.muscle_left_leg_pct = Some(TanitaParser::parse_f32(value)),
-                "mT" => data_raw.muscle_trunk_pct = Some(TanitaParser::parse_f32(value)),
-
-                "bw" => data_raw.bone_kg = Some(TanitaParser::parse_f32(value)),
-                "ww" => data_raw.water_percent = Some(TanitaParser::parse_f32(value)),
-                "IF" => data_raw.visceral_fat_rating = Some(TanitaParser::parse_u8(value)),
-                "rA" => data_raw.metabolic_age_years = Some(TanitaParser::parse_u8(value)),
-                "rD" => data_raw.daily_calorie_intake_kcal = Some(TanitaParser::parse_u16(value)),
-                "CS" => data_raw.checksum = TanitaParser::unquote(value),
-
-                _ => {
-                    println!("[DATA] Some extra key: {:?} and value: {:?}", key, value);
-                    data_raw.extras.push((key.to_string(), value.to_string()));
+            match model_profile.resolve(key, config) {
+                Some(ModelField::Tag(FieldTag::Model)) => {
+                    data_raw.model = TanitaParser::unquote(value)
+                }
+                Some(ModelField::Tag(FieldTag::Date)) => {
+                    data_raw.date_dmy = TanitaParser::unquote(value)
                 }
+                Some(ModelField::Tag(FieldTag::Time)) => {
+                    data_raw.time_hms = TanitaParser::unquote(value)
+                }
+                Some(ModelField::Tag(FieldTag::Gender)) => {
+                    data_raw.gender_code =
+                        TanitaParser::parse_number(key, value, &mut data_raw.parse_warnings)
+                }
+                Some(ModelField::Tag(FieldTag::Age)) => {
+                    data_raw.age_years =
+                        TanitaParser::parse_number(key, value, &mut data_raw.parse_warnings)
+                }
+                Some(ModelField::Tag(FieldTag::Height)) => {
+                    data_raw.height_cm =
+                        TanitaParser::parse_number(key, value, &mut data_raw.parse_warnings)
+                }
+
+                Some(ModelField::Tag(FieldTag::ActivityLevel)) => {
+                    data_raw.activity_level_code =
+                        TanitaParser::parse_number(key, value, &mut data_raw.parse_warnings)
+                }
+                Some(ModelField::Tag(FieldTag::BodyType)) => {
+                    data_raw.body_type_code =
+                        TanitaParser::parse_number(key, value, &mut data_raw.parse_warnings)
+                }
+                Some(ModelField::Tag(FieldTag::Weight)) => {
+                    data_raw.weight_kg =
+                        TanitaParser::parse_number(key, value, &mut data_raw.parse_warnings)
+                }
+                Some(ModelField::Tag(FieldTag::Bmi)) => {
+                    data_raw.bmi =
+                        TanitaParser::parse_number(key, value, &mut data_raw.parse_warnings)
+                }
+
+                Some(ModelField::Tag(FieldTag::FatPercent)) => {
+                    data_raw.fat_percent =
+                        TanitaParser::parse_number(key, value, &mut data_raw.parse_warnings)
+                }
+                Some(ModelField::Tag(FieldTag::FatRightArm)) => {
+                    data_raw.fat_right_arm_pct =
+                        TanitaParser::parse_present_f32(key, value, &mut data_raw.parse_warnings)
+                }
+                Some(ModelField::Tag(FieldTag::FatLeftArm)) => {
+                    data_raw.fat_left_arm_pct =
+                        TanitaParser::parse_present_f32(key, value, &mut data_raw.parse_warnings)
+                }
+                Some(ModelField::Tag(FieldTag::FatRightLeg)) => {
+                    data_raw.fat_right_leg_pct =
+                        TanitaParser::parse_present_f32(key, value, &mut data_raw.parse_warnings)
+                }
+                Some(ModelField::Tag(FieldTag::FatLeftLeg)) => {
+                    data_raw.fat_left_leg_pct =
+                        TanitaParser::parse_present_f32(key, value, &mut data_raw.parse_warnings)
+                }
+                Some(ModelField::Tag(FieldTag::FatTrunk)) => {
+                    data_raw.fat_trunk_pct =
+                        TanitaParser::parse_present_f32(key, value, &mut data_raw.parse_warnings)
+                }
+
+                Some(ModelField::Tag(FieldTag::MusclePercent)) => {
+                    data_raw.muscle_percent = Some(TanitaParser::parse_number(
+                        key,
+                        value,
+                        &mut data_raw.parse_warnings,
+                    ))
+                }
+                Some(ModelField::Tag(FieldTag::MuscleLeftArm)) => {
+                    data_raw.muscle_left_arm_pct = Some(TanitaParser::parse_number(
+                        key,
+                        value,
+                        &mut data_raw.parse_warnings,
+                    ))
+                }
+                Some(ModelField::Tag(FieldTag::MuscleRightArm)) => {
+                    data_raw.muscle_right_arm_pct = Some(TanitaParser::parse_number(
+                        key,
+                        value,
+                        &mut data_raw.parse_warnings,
+                    ))
+                }
+                Some(ModelField::Tag(FieldTag::MuscleRightLeg)) => {
+                    data_raw.muscle_right_leg_pct = Some(TanitaParser::parse_number(
+                        key,
+                        value,
+                        &mut data_raw.parse_warnings,
+                    ))
+                }
+                Some(ModelField::Tag(FieldTag::MuscleLeftLeg)) => {
+                    data_raw.muscle_left_leg_pct = Some(TanitaParser::parse_number(
+                        key,
+                        value,
+                        &mut data_raw.parse_warnings,
+                    ))
+                }
+                Some(ModelField::Tag(FieldTag::MuscleTrunk)) => {
+                    data_raw.muscle_trunk_pct = Some(TanitaParser::parse_number(
+                        key,
+                        value,
+                        &mut data_raw.parse_warnings,
+                    ))
+                }
+
+                Some(ModelField::Tag(FieldTag::BoneMass)) => {
+                    data_raw.bone_kg = Some(TanitaParser::parse_number(
+                        key,
+                        value,
+                        &mut data_raw.parse_warnings,
+                    ))
+                }
+                Some(ModelField::Tag(FieldTag::WaterPercent)) => {
+                    data_raw.water_percent = Some(TanitaParser::parse_number(
+                        key,
+                        value,
+                        &mut data_raw.parse_warnings,
+                    ))
+                }
+                Some(ModelField::Tag(FieldTag::VisceralFat)) => {
+                    data_raw.visceral_fat_rating = Some(TanitaParser::parse_number(
+                        key,
+                        value,
+                        &mut data_raw.parse_warnings,
+                    ))
+                }
+                Some(ModelField::Tag(FieldTag::MetabolicAge)) => {
+                    data_raw.metabolic_age_years = Some(TanitaParser::parse_number(
+                        key,
+                        value,
+                        &mut data_raw.parse_warnings,
+                    ))
+                }
+                Some(ModelField::Tag(FieldTag::DailyCalorieIntake)) => {
+                    data_raw.daily_calorie_intake_kcal = Some(TanitaParser::parse_number(
+                        key,
+                        value,
+                        &mut data_raw.parse_warnings,
+                    ))
+                }
+                Some(ModelField::Tag(FieldTag::Checksum)) => {
+                    data_raw.checksum = TanitaParser::unquote(value)
+                }
+
+                Some(ModelField::PhaseAngle) => {
+                    data_raw.phase_angle = Some(TanitaParser::parse_number(
+                        key,
+                        value,
+                        &mut data_raw.parse_warnings,
+                    ))
+                }
+                Some(ModelField::Impedance) => {
+                    data_raw.impedance_ohms = Some(TanitaParser::parse_number(
+                        key,
+                        value,
+                        &mut data_raw.parse_warnings,
+                    ))
+                }
+
+                _ => data_raw.extras.push((key.to_string(), value.to_string())),
             }
             key_pointer += 2;
         }
@@ -362,3 +1451,258 @@ impl DataRaw {
         data_raw
     }
 }
+
+// Compile-time check that the parser and everything it produces can be
+// shared across threads.
+const _: () = {
+    fn assert_send_sync<T: Send + Sync>() {}
+    let _ = assert_send_sync::<TanitaParser>;
+    let _ = assert_send_sync::<ParseProgress>;
+    let _ = assert_send_sync::<ParserConfig>;
+    let _ = assert_send_sync::<TanitaPair>;
+    let _ = assert_send_sync::<RawUserRecord>;
+    let _ = assert_send_sync::<ProfRaw>;
+    let _ = assert_send_sync::<DataRaw>;
+    let _ = assert_send_sync::<TanitaValidationError>;
+};
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    const PROFILE_ROW: &str = r#"MO,"BC-601",DB,"14/06/1991",Bt,0,GE,1,Hm,180.0,AL,2,CS,38"#;
+
+    /// `row` with the `CS` that `TanitaParser::verify_checksum` accepts.
+    pub(crate) fn with_checksum(row: &str) -> String {
+        let checksum = row.bytes().fold(0, |acc, byte| acc ^ byte);
+        format!("{},CS,{:02X}", row, checksum)
+    }
+
+    /// A throwaway GRAPHV1 folder with a PROF/DATA pair per `(index, prof, data)`.
+    pub(crate) fn card(name: &str, users: &[(usize, &str, &str)]) -> PathBuf {
+        let root = std::env::temp_dir().join(format!("tanita-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(root.join(DATA_FOLDER_NAME)).unwrap();
+        fs::create_dir_all(root.join(PROFILE_FOLDER_NAME)).unwrap();
+        for (index, prof, data) in users {
+            let file = |prefix: &str| format!("{}{}{}", prefix, index, CSV_EXTENTION_NAME);
+            fs::write(
+                root.join(PROFILE_FOLDER_NAME)
+                    .join(file(PROFILE_FILE_NAME_PREFIX)),
+                prof,
+            )
+            .unwrap();
+            fs::write(
+                root.join(DATA_FOLDER_NAME)
+                    .join(file(DATA_FILE_NAME_PREFIX)),
+                data,
+            )
+            .unwrap();
+        }
+        root
+    }
+
+    #[test]
+    fn height_in_meters_is_reported() {
+        let meters = PROFILE_ROW.replace("180.0", "1.75");
+        let root = card("height", &[(1, &meters, ""), (2, PROFILE_ROW, "")]);
+        let dataset = TanitaParser::new(root.clone())
+            .get_raw_users_records()
+            .unwrap();
+        let heights: Vec<_> = dataset
+            .warnings
+            .iter()
+            .filter_map(|warning| match warning.kind {
+                WarningKind::HeightInMeters { height, height_cm } => {
+                    Some((warning.index, height, height_cm))
+                }
+                _ => None,
+            })
+            .collect();
+        assert_eq!(heights, [(0, 1.75, 175.0)]);
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    /// A full-length BC-601 DATA row, every field the scale writes.
+    const FULL_ROW: &str = r#"{0,16,~0,1,~1,1,~2,1,MO,"BC-601",DT,"14/03/2021",Ti,"07:12:45",Bt,0,GE,2,AG,34,Hm,166.0,AL,2,Wk,59.1,MI,21.4,FW,26.8,Fr,25.6,Fl,26.2,FR,29.7,FL,29.9,FT,25.9,mW,41.2,mr,2.2,ml,2.2,mR,7.3,mL,7.3,mT,22.2,bW,2.2,IF,3,rB,1319,rA,32,ww,52.3,CS,21"#;
+
+    #[test]
+    fn checksum_is_the_xor_before_the_cs_comma() {
+        assert!(TanitaParser::verify_checksum(FULL_ROW));
+        assert!(TanitaParser::verify_checksum(&format!("{}\r", FULL_ROW)));
+        // the `,` in front of `CS` is not part of it
+        let with_comma = FULL_ROW.replace(",CS,21", ",CS,0D");
+        assert!(!TanitaParser::verify_checksum(&with_comma));
+        assert!(!TanitaParser::verify_checksum(
+            &FULL_ROW.replace("Wk,59.1", "Wk,59.7")
+        ));
+    }
+
+    #[test]
+    fn truncated_row_fails_the_checksum() {
+        let cut = &FULL_ROW[..FULL_ROW.find("mW,41.2").unwrap() + 5];
+        assert!(cut.ends_with("mW,41"));
+        assert!(!TanitaParser::verify_checksum(cut));
+        let data = DataRaw::from_csv_row(cut, &ParserConfig::default());
+        assert!(!data.checksum_ok);
+        // cut short with the old `CS` written after it
+        let checksum = &FULL_ROW[FULL_ROW.rfind(',').unwrap()..];
+        assert!(!TanitaParser::verify_checksum(&format!(
+            "{}{}",
+            cut, checksum
+        )));
+    }
+
+    #[test]
+    fn blank_segmental_value_is_missing_not_zero() {
+        let row = r#"MO,"BC-601",DT,"01/01/2020",Ti,"08:00:00",Wk,80.0,FW,20.0,Fr,,Fl,12.5,CS,31"#;
+        let data = DataRaw::from_csv_row(row, &ParserConfig::default());
+        assert_eq!(data.fat_right_arm_pct, None);
+        assert_eq!(data.fat_left_arm_pct, Some(12.5));
+        assert!(data.parse_warnings.is_empty());
+        assert!(data.checksum_ok);
+    }
+
+    #[test]
+    fn repeated_key_keeps_the_first_value_and_warns() {
+        let row =
+            with_checksum(r#"MO,"BC-601",DT,"01/01/2020",Ti,"08:00:00",Wk,80.0,FW,20.0,Wk,95.0"#);
+        let root = card("duplicate-key", &[(1, PROFILE_ROW, &row)]);
+        let dataset = TanitaParser::new(root.clone())
+            .get_raw_users_records()
+            .unwrap();
+        assert_eq!(dataset.users[0].data[0].weight_kg, 80.0);
+        let warning = &dataset.warnings[0];
+        assert_eq!((warning.index, warning.line), (0, 1));
+        assert_eq!(
+            warning.kind,
+            WarningKind::DuplicateKey {
+                key: "Wk".to_string()
+            }
+        );
+        assert_eq!(dataset.warnings.len(), 1);
+        assert_eq!(
+            warning.to_string(),
+            r#"User 1 line 1: repeated key "Wk", the first value was used"#
+        );
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn mixed_line_endings_leave_no_stray_cr() {
+        let row = |date: &str| {
+            with_checksum(&format!(
+                r#"MO,"BC-601",DT,"{}",Ti,"08:00:00",Wk,80.0,FW,20.0"#,
+                date
+            ))
+        };
+        let data = format!(
+            "{}\r\n{}\n{}\r\n",
+            row("01/01/2020"),
+            row("02/01/2020"),
+            row("03/01/2020")
+        );
+        let root = card("line-endings", &[(1, PROFILE_ROW, &data)]);
+        let dataset = TanitaParser::new(root.clone())
+            .get_raw_users_records()
+            .unwrap();
+        let rows = &dataset.users[0].data;
+        assert_eq!(rows.len(), 3);
+        for data in rows {
+            assert_eq!(data.model, "BC-601");
+            assert!(data.checksum_ok);
+        }
+        fs::remove_dir_all(root).unwrap();
+
+        let cr_row = format!("{}\r", row("04/01/2020"));
+        let data = DataRaw::from_csv_row(&cr_row, &ParserConfig::default());
+        assert_eq!(data.checksum, &cr_row[cr_row.len() - 3..cr_row.len() - 1]);
+        assert!(data.checksum_ok);
+    }
+
+    #[test]
+    fn picking_the_data_folder_uses_its_parent() {
+        let root = card("subfolder", &[(1, PROFILE_ROW, "")]);
+        let parser = TanitaParser::new(root.join(DATA_FOLDER_NAME));
+        assert_eq!(parser.root_dir, root);
+        assert_eq!(parser.picked_subfolder, Some(root.join(DATA_FOLDER_NAME)));
+        assert_eq!(TanitaParser::new(root.clone()).picked_subfolder, None);
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn profile_without_data_is_not_an_empty_data_file() {
+        let root = card(
+            "missing-data",
+            &[(1, PROFILE_ROW, ""), (2, PROFILE_ROW, "")],
+        );
+        fs::remove_file(root.join(DATA_FOLDER_NAME).join("DATA2.CSV")).unwrap();
+        let mut parser = TanitaParser::new(root.clone());
+        parser.config.allow_profiles_without_data = true;
+        let dataset = parser.get_raw_users_records().unwrap();
+        let kinds: Vec<_> = dataset
+            .warnings
+            .iter()
+            .map(|warning| (warning.index, warning.kind.clone()))
+            .collect();
+        assert_eq!(
+            kinds,
+            [
+                (0, WarningKind::EmptyDataFile),
+                (1, WarningKind::MissingDataFile)
+            ]
+        );
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn progress_is_reported_while_reading_data() {
+        let row = r#"MO,"BC-601",DT,"01/01/2020",Ti,"08:00:00",Wk,80.0,FW,20.0,CS,37"#;
+        let data = vec![row; 600].join("\n");
+        let root = card("progress", &[(1, PROFILE_ROW, &data), (2, PROFILE_ROW, "")]);
+        let (tx, rx) = std::sync::mpsc::channel();
+        let mut parser = TanitaParser::new(root.clone());
+        parser.progress = Some(tx);
+        parser.get_raw_users_records().unwrap();
+        drop(parser);
+        let reports: Vec<_> = rx.iter().map(|p| (p.user, p.users, p.lines)).collect();
+        assert_eq!(reports, [(0, 2, 256), (0, 2, 512), (0, 2, 600), (1, 2, 0)]);
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn pairs_read_whole_or_line_by_line_alike() {
+        let data = format!("{}\r\n{}\n", PROFILE_ROW, PROFILE_ROW);
+        let root = card("pairs", &[(1, PROFILE_ROW, &data), (3, PROFILE_ROW, "")]);
+        let pairs = TanitaParser::new(root.clone()).pairs().unwrap();
+        assert_eq!(
+            pairs.iter().map(TanitaPair::index).collect::<Vec<_>>(),
+            [0, 2]
+        );
+        let content = pairs[0].get_data_file_content().unwrap();
+        let streamed: Vec<String> = pairs[0]
+            .data_file_lines()
+            .unwrap()
+            .unwrap()
+            .map(Result::unwrap)
+            .collect();
+        assert_eq!(content.lines().collect::<Vec<_>>(), streamed);
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn card_folder_is_found_only_near_the_mount_root() {
+        let mount = std::env::temp_dir().join(format!("tanita-mount-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&mount);
+        let graph = mount.join("tanita").join("GraphV1");
+        fs::create_dir_all(graph.join(DATA_FOLDER_NAME)).unwrap();
+        fs::create_dir_all(graph.join(PROFILE_FOLDER_NAME)).unwrap();
+        assert_eq!(find_card_folder(&mount), Some(graph.clone()));
+
+        let deep = mount.join("backup");
+        fs::rename(mount.join("tanita"), &deep).unwrap();
+        assert_eq!(find_card_folder(&mount), None);
+        assert_eq!(find_tanita_folders(&mount), [deep.join("GraphV1")]);
+        fs::remove_dir_all(mount).unwrap();
+    }
+}
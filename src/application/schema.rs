@@ -0,0 +1,437 @@
+use serde_json::{Map, Value, json};
+
+use super::edits::Field;
+
+const DRAFT: &str = "https://json-schema.org/draft/2020-12/schema";
+
+/// JSON Schema (draft 2020-12) of one `UserMeasurements` as serde writes it,
+/// e.g. an element of a "Save debug dump" file. Fields that can be `null` take
+/// `"null"` as one of their types; units are given in the descriptions.
+/// Every field is always written, so all are listed as required.
+pub fn user_measurements_schema() -> Value {
+    let mut schema = object(
+        "One user of the scale: the DATA{N}.CSV/PROF{N}.CSV pair, read and interpreted.",
+        [
+            (
+                "index",
+                integer("Pair index N, from the DATA{N}.CSV/PROF{N}.CSV file names."),
+            ),
+            (
+                "profile",
+                reference("Profile", "The selected profile revision."),
+            ),
+            (
+                "profile_revisions",
+                json!({
+                    "type": "array",
+                    "description": "Every revision found in PROF{N}.CSV, oldest first.",
+                    "items": { "$ref": "#/$defs/Profile" },
+                }),
+            ),
+            (
+                "selected_revision",
+                integer("Index into `profile_revisions`."),
+            ),
+            (
+                "measurements",
+                json!({
+                    "type": "array",
+                    "description": "Rows of DATA{N}.CSV, oldest first.",
+                    "items": { "$ref": "#/$defs/Measurement" },
+                }),
+            ),
+            (
+                "data_file_empty",
+                boolean("DATA{N}.CSV had no rows at all, as opposed to rows that failed to parse."),
+            ),
+        ],
+    );
+    schema["$schema"] = json!(DRAFT);
+    schema["title"] = json!("UserMeasurements");
+    schema["$defs"] = json!({
+        "Date": object("Calendar date.", [
+            ("years", integer("Year, e.g. 2020.")),
+            ("months", integer("Month, 1-12.")),
+            ("days", integer("Day of the month, 1-31.")),
+        ]),
+        "Time": object("Time of day.", [
+            ("hours", integer("Hours, 0-23.")),
+            ("minutes", integer("Minutes, 0-59.")),
+            ("seconds", integer("Seconds, 0-59.")),
+        ]),
+        "DateTime": object("Local date and time set on the scale.", [
+            ("date", reference("Date", "Date part.")),
+            ("time", reference("Time", "Time part.")),
+        ]),
+        "Percent": number("Percentage, 0-100."),
+        "Gender": {
+            "description": "`GE`: \"Male\" (1), \"Female\" (2), or {\"Other\": code}.",
+            "oneOf": [
+                { "enum": ["Male", "Female"] },
+                object("Any other `GE` code.", [("Other", integer("Device code."))]),
+            ],
+        },
+        "DeviceModel": {
+            "description": "`MO`: a known model, or {\"Unknown\": value} as written.",
+            "oneOf": [
+                { "enum": ["Bc601", "Bc603", "Bc545"] },
+                object("Any other `MO` value.", [("Unknown", string("`MO` as written, empty when missing."))]),
+            ],
+        },
+        "ParseWarning": object("A value that didn't read as a number and was taken as 0.", [
+            ("key", string("Field code, e.g. \"Wk\".")),
+            ("raw_value", string("The value as written.")),
+            ("reason", string("Why it didn't read.")),
+        ]),
+        "Profile": object("PROF{N}.CSV, as the scale stores the user.", [
+            ("model", reference("DeviceModel", "`MO`, the scale model.")),
+            ("birth_date_dmy", reference("Date", "`DB`, date of birth.")),
+            ("gender", reference("Gender", "`GE`.")),
+            ("height_cm", number("`Hm`, height in cm.")),
+            ("activity_level_code", integer("`AL`: 1 low, 2 medium, 3 high.")),
+            ("body_type_code", integer("`Bt`: 0 standard, 2 athlete.")),
+        ]),
+        "Measurement": measurement(),
+    });
+    schema
+}
+
+fn measurement() -> Value {
+    let percent = |description: &str| reference("Percent", description);
+    let edited_fields: Vec<Value> = Field::ALL
+        .iter()
+        .map(|field| serde_json::to_value(field).expect("unit variants serialize"))
+        .collect();
+    object(
+        "One DATA{N}.CSV row.",
+        [
+            (
+                "date_time",
+                nullable(reference(
+                    "DateTime",
+                    "`DT`/`Ti`; null when the scale's clock was unset (00/00/0000).",
+                )),
+            ),
+            ("source_line", integer("1-based line in DATA{N}.CSV.")),
+            (
+                "raw_row",
+                nullable(string("The DATA line as read, when the parser kept it.")),
+            ),
+            (
+                "gender",
+                reference("Gender", "`GE` echoed at measurement time."),
+            ),
+            ("age_years", integer("`AG`, age in years.")),
+            ("height_cm", number("`Hm`, height in cm.")),
+            (
+                "activity_level_code",
+                integer("`AL`: 1 low, 2 medium, 3 high."),
+            ),
+            ("body_type_code", integer("`Bt`: 0 standard, 2 athlete.")),
+            ("weight_kg", number("`Wk`, weight in kg.")),
+            ("bmi", number("`MI`, body mass index in kg/m².")),
+            ("fat_percent", percent("`FW`, body fat in %.")),
+            (
+                "fat_right_arm_pct",
+                nullable(percent("`Fr`, right arm fat in %.")),
+            ),
+            (
+                "fat_left_arm_pct",
+                nullable(percent("`Fl`, left arm fat in %.")),
+            ),
+            (
+                "fat_right_leg_pct",
+                nullable(percent("`FR`, right leg fat in %.")),
+            ),
+            (
+                "fat_left_leg_pct",
+                nullable(percent("`FL`, left leg fat in %.")),
+            ),
+            ("fat_trunk_pct", nullable(percent("`FT`, trunk fat in %."))),
+            ("muscle_percent", nullable(percent("`mW`, muscle in %."))),
+            (
+                "muscle_right_arm_pct",
+                nullable(percent("`mr`, right arm muscle in %.")),
+            ),
+            (
+                "muscle_left_arm_pct",
+                nullable(percent("`ml`, left arm muscle in %.")),
+            ),
+            (
+                "muscle_right_leg_pct",
+                nullable(percent("`mR`, right leg muscle in %.")),
+            ),
+            (
+                "muscle_left_leg_pct",
+                nullable(percent("`mL`, left leg muscle in %.")),
+            ),
+            (
+                "muscle_trunk_pct",
+                nullable(percent("`mT`, trunk muscle in %.")),
+            ),
+            ("bone_kg", nullable(number("`bw`, bone mass in kg."))),
+            ("water_percent", nullable(percent("`ww`, body water in %."))),
+            (
+                "visceral_fat_rating",
+                nullable(integer("`IF`, visceral fat rating, 1-59.")),
+            ),
+            (
+                "metabolic_age_years",
+                nullable(integer("`rA`, metabolic age in years.")),
+            ),
+            (
+                "daily_calorie_intake_kcal",
+                nullable(integer("`rD`, daily calorie intake in kcal.")),
+            ),
+            ("phase_angle", nullable(number("Phase angle in degrees."))),
+            ("impedance_ohms", nullable(number("Impedance in ohms."))),
+            (
+                "extras",
+                json!({
+                    "type": "array",
+                    "description": "Tags the parser didn't recognise, as [key, value].",
+                    "items": {
+                        "type": "array",
+                        "prefixItems": [{ "type": "string" }, { "type": "string" }],
+                        "items": false,
+                    },
+                }),
+            ),
+            (
+                "parse_warnings",
+                json!({
+                    "type": "array",
+                    "description": "Values of this row taken as 0.",
+                    "items": { "$ref": "#/$defs/ParseWarning" },
+                }),
+            ),
+            (
+                "possibly_wrong_user",
+                boolean("Echoed gender or height disagrees with the profile."),
+            ),
+            ("checksum_ok", boolean("`CS` matched the row.")),
+            ("excluded", boolean("Left out of charts and stats by hand.")),
+            ("milestone", boolean("Starred by hand.")),
+            (
+                "original",
+                json!({
+                    "type": "object",
+                    "description": "Device values of fields corrected by hand, in the field's \
+                                    own unit (kg or %); null when the device had none.",
+                    "propertyNames": { "enum": edited_fields },
+                    "additionalProperties": { "type": ["number", "null"] },
+                }),
+            ),
+        ],
+    )
+}
+
+/// A closed object; serde writes every field, so all are required.
+fn object<const N: usize>(description: &str, properties: [(&str, Value); N]) -> Value {
+    let required: Vec<&str> = properties.iter().map(|(name, _)| *name).collect();
+    let properties: Map<String, Value> = properties
+        .into_iter()
+        .map(|(name, schema)| (name.to_string(), schema))
+        .collect();
+    json!({
+        "type": "object",
+        "description": description,
+        "properties": properties,
+        "required": required,
+        "additionalProperties": false,
+    })
+}
+
+fn typed(kind: &str, description: &str) -> Value {
+    json!({ "type": kind, "description": description })
+}
+
+fn number(description: &str) -> Value {
+    typed("number", description)
+}
+
+fn integer(description: &str) -> Value {
+    json!({ "type": "integer", "minimum": 0, "description": description })
+}
+
+fn string(description: &str) -> Value {
+    typed("string", description)
+}
+
+fn boolean(description: &str) -> Value {
+    typed("boolean", description)
+}
+
+fn reference(name: &str, description: &str) -> Value {
+    json!({ "$ref": format!("#/$defs/{}", name), "description": description })
+}
+
+/// `schema`, also accepting `null`.
+fn nullable(schema: Value) -> Value {
+    match schema.get("type").and_then(Value::as_str) {
+        Some(kind) => {
+            let mut schema = schema.clone();
+            schema["type"] = json!([kind, "null"]);
+            schema
+        }
+        None => json!({
+            "description": schema["description"],
+            "anyOf": [{ "$ref": schema["$ref"] }, { "type": "null" }],
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+    use crate::application::UserMeasurements;
+    use crate::application::parser::{TanitaParser, tests::card};
+
+    /// The subset of JSON Schema `user_measurements_schema` uses. Returns the
+    /// path of the first value that doesn't match.
+    fn check(schema: &Value, value: &Value, root: &Value, path: &str) -> Result<(), String> {
+        let fail = || Err(format!("{} doesn't match {}", path, schema));
+        if let Some(target) = schema.get("$ref").and_then(Value::as_str) {
+            let name = target.trim_start_matches("#/$defs/");
+            return check(&root["$defs"][name], value, root, path);
+        }
+        if let Some(options) = schema.get("anyOf").or(schema.get("oneOf")) {
+            let matching = options
+                .as_array()
+                .unwrap()
+                .iter()
+                .filter(|option| check(option, value, root, path).is_ok())
+                .count();
+            return if matching > 0 { Ok(()) } else { fail() };
+        }
+        if let Some(allowed) = schema.get("enum") {
+            return if allowed.as_array().unwrap().contains(value) {
+                Ok(())
+            } else {
+                fail()
+            };
+        }
+        if let Some(kind) = schema.get("type") {
+            let kinds: Vec<&str> = match kind {
+                Value::Array(kinds) => kinds.iter().filter_map(Value::as_str).collect(),
+                kind => vec![kind.as_str().unwrap()],
+            };
+            let actual = match value {
+                Value::Null => "null",
+                Value::Bool(_) => "boolean",
+                Value::Number(n) if n.is_u64() || n.is_i64() => "integer",
+                Value::Number(_) => "number",
+                Value::String(_) => "string",
+                Value::Array(_) => "array",
+                Value::Object(_) => "object",
+            };
+            if !kinds
+                .iter()
+                .any(|kind| *kind == actual || (*kind == "number" && actual == "integer"))
+            {
+                return fail();
+            }
+        }
+        match value {
+            Value::Object(fields) => {
+                let properties = schema.get("properties").and_then(Value::as_object);
+                for name in schema["required"].as_array().into_iter().flatten() {
+                    if !fields.contains_key(name.as_str().unwrap()) {
+                        return Err(format!("{} misses {}", path, name));
+                    }
+                }
+                for (name, field) in fields {
+                    let path = format!("{}.{}", path, name);
+                    if let Some(names) = schema.get("propertyNames") {
+                        check(names, &json!(name), root, &path)?;
+                    }
+                    match (
+                        properties.and_then(|p| p.get(name)),
+                        &schema["additionalProperties"],
+                    ) {
+                        (Some(property), _) => check(property, field, root, &path)?,
+                        (None, Value::Bool(false)) => {
+                            return Err(format!("{} is not in the schema", path));
+                        }
+                        (None, Value::Null) => {}
+                        (None, additional) => check(additional, field, root, &path)?,
+                    }
+                }
+                Ok(())
+            }
+            Value::Array(items) => {
+                let prefix = schema["prefixItems"].as_array().map_or(0, Vec::len);
+                for (i, item) in items.iter().enumerate() {
+                    let item_schema = if i < prefix {
+                        &schema["prefixItems"][i]
+                    } else {
+                        &schema["items"]
+                    };
+                    match item_schema {
+                        Value::Bool(false) => return fail(),
+                        Value::Null => {}
+                        item_schema => check(item_schema, item, root, &format!("{}[{}]", path, i))?,
+                    }
+                }
+                Ok(())
+            }
+            _ => Ok(()),
+        }
+    }
+
+    #[test]
+    fn schema_matches_serialized_users() {
+        const PROFILE_ROW: &str = r#"MO,"BC-601",DB,"14/06/1991",Bt,0,GE,1,Hm,180.0,AL,2,CS,38"#;
+        let data = [
+            r#"MO,"BC-601",DT,"01/01/2020",Ti,"08:00:00",GE,1,AG,28,Hm,180.0,AL,2,Bt,0,Wk,80.0,MI,24.7,FW,20.0,Fr,18.5,Fl,18.0,FR,19.0,FL,19.5,FT,21.0,mW,60.1,mr,3.1,ml,3.0,mR,10.2,mL,10.1,mT,30.0,bw,3.2,ww,55.0,IF,6,rA,25,rD,2500,Zx,3,CS,2B"#,
+            r#"MO,"BC-601",DT,"00/00/0000",Ti,"08:00:00",Wk,8x.0,FW,20.5,CS,7A"#,
+        ]
+        .join("\n");
+        let root = card(
+            "schema",
+            &[
+                (1, PROFILE_ROW, &data),
+                (
+                    2,
+                    &PROFILE_ROW.replace("GE,1", "GE,7").replace("BC-601", "XY"),
+                    "",
+                ),
+            ],
+        );
+        let mut parser = TanitaParser::new(root.clone());
+        parser.config.keep_raw = true;
+        let users: Vec<UserMeasurements> = parser
+            .get_raw_users_records()
+            .unwrap()
+            .users
+            .into_iter()
+            .filter_map(UserMeasurements::from_raw)
+            .collect();
+        fs::remove_dir_all(root).unwrap();
+        assert_eq!(users.len(), 2);
+
+        let schema = user_measurements_schema();
+        for user in serde_json::to_value(&users).unwrap().as_array().unwrap() {
+            check(&schema, user, &schema, "$").unwrap();
+        }
+    }
+
+    #[test]
+    fn schema_rejects_a_missing_field() {
+        let schema = user_measurements_schema();
+        let mut profile = json!({
+            "model": "Bc601",
+            "birth_date_dmy": { "years": 1991, "months": 6, "days": 14 },
+            "gender": { "Other": 7 },
+            "height_cm": 180.0,
+            "activity_level_code": 2,
+            "body_type_code": 0,
+        });
+        let profile_schema = &schema["$defs"]["Profile"];
+        assert!(check(profile_schema, &profile, &schema, "$").is_ok());
+        profile.as_object_mut().unwrap().remove("height_cm");
+        assert!(check(profile_schema, &profile, &schema, "$").is_err());
+    }
+}
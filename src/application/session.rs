@@ -0,0 +1,122 @@
+use std::fmt::Write;
+
+use super::UserMeasurements;
+use super::export::csv_record;
+use super::general_data_structs::DateTime;
+use super::metric::Metric;
+use super::stats::ChangeStat;
+
+/// One metric's values at both ends of a session comparison.
+#[derive(Debug, Clone, Copy)]
+pub struct SessionRow {
+    pub start: f32,
+    pub end: f32,
+    pub change: ChangeStat,
+}
+
+/// Before/after summary between two check-ins, as handed to a client.
+#[derive(Debug, Clone)]
+pub struct SessionComparison {
+    /// Timestamps of the measurements actually compared, which are the ones
+    /// nearest to the requested bounds.
+    pub start: DateTime,
+    pub end: DateTime,
+    pub rows: Vec<SessionRow>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SessionFormat {
+    Csv,
+    Text,
+}
+
+impl SessionFormat {
+    pub fn file_extension(&self) -> &'static str {
+        match self {
+            SessionFormat::Csv => "csv",
+            SessionFormat::Text => "txt",
+        }
+    }
+}
+
+impl UserMeasurements {
+    /// Compares the measurements nearest to `from` and `to`. Metrics missing
+    /// from either end are left out. `None` when both bounds resolve to the
+    /// same measurement or there is nothing dated to compare.
+    pub fn session_comparison(&self, from: &DateTime, to: &DateTime) -> Option<SessionComparison> {
+        let first = self.nearest_measurement(from)?;
+        let last = self.nearest_measurement(to)?;
+        let start = first.date_time.clone()?;
+        let end = last.date_time.clone()?;
+
+        let rows: Vec<SessionRow> = Metric::ALL
+            .iter()
+            .filter_map(|metric| {
+                let start_value = metric.value(first)?;
+                let end_value = metric.value(last)?;
+                Some(SessionRow {
+                    start: start_value,
+                    end: end_value,
+                    change: ChangeStat::between(*metric, (&start, start_value), (&end, end_value))?,
+                })
+            })
+            .collect();
+
+        (!rows.is_empty()).then_some(SessionComparison { start, end, rows })
+    }
+}
+
+/// Values are rounded to what the scale displays.
+fn round(value: f32, decimals: i32) -> f32 {
+    let factor = 10f32.powi(decimals);
+    (value * factor).round() / factor
+}
+
+impl SessionComparison {
+    pub fn render(&self, format: SessionFormat) -> String {
+        match format {
+            SessionFormat::Csv => self.to_csv(),
+            SessionFormat::Text => self.to_text(),
+        }
+    }
+
+    fn to_csv(&self) -> String {
+        let mut out = csv_record(["metric", "unit", "start", "end", "change", "per_week"]);
+        for row in &self.rows {
+            out.push_str(&csv_record([
+                row.change.metric.to_string(),
+                row.change.metric.unit().to_string(),
+                round(row.start, 1).to_string(),
+                round(row.end, 1).to_string(),
+                round(row.change.total, 1).to_string(),
+                round(row.change.per_week, 2).to_string(),
+            ]));
+        }
+        out
+    }
+
+    fn to_text(&self) -> String {
+        let mut out = format!(
+            "Since {} (to {}, {} days)\n",
+            self.start,
+            self.end,
+            self.rows
+                .first()
+                .map(|row| row.change.span_days.round())
+                .unwrap_or_default()
+        );
+        for row in &self.rows {
+            let unit = row.change.metric.unit();
+            let _ = writeln!(
+                out,
+                "  {:<12} {:>7} -> {:>7} {:<2} ({:+})",
+                row.change.metric.to_string(),
+                round(row.start, 1),
+                round(row.end, 1),
+                unit,
+                round(row.change.total, 1)
+            );
+        }
+        out
+    }
+}
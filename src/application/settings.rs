@@ -0,0 +1,133 @@
+use std::{collections::BTreeMap, fs, path::PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use super::general_data_structs::DateTime;
+use super::merge::MergeTolerance;
+use super::metric::Metric;
+use super::number_format::NumberFormat;
+use super::units::Units;
+use super::{UserSort, ViewFilter};
+
+const CONFIG_DIR_NAME: &str = "tanita-601";
+const CONFIG_FILE_NAME: &str = "settings.json";
+
+/// User preferences kept across runs in one file under the OS config dir.
+/// Every field falls back to its default, so older or partial files still load.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    pub user_sort: UserSort,
+    pub number_format: NumberFormat,
+    pub show_profile_columns: bool,
+    pub compact_table: bool,
+    /// Keep each measurement's original DATA line; applies to the next load.
+    pub keep_raw_rows: bool,
+    /// Show recomputed age and BMI in place of the values the device reported.
+    #[serde(alias = "prefer_computed_age")]
+    pub show_recomputed: bool,
+    /// Only list and chart measurements that include muscle data.
+    pub muscle_only: bool,
+    /// Start the view at the first measurement with muscle data.
+    pub full_feature_era_only: bool,
+    /// Black and white theme with a bright accent, for low vision.
+    pub high_contrast: bool,
+    /// Table text and column scale, `FONT_SCALE_RANGE`.
+    pub font_scale: f32,
+    /// Show the segmental fat distribution bar on each row.
+    pub show_fat_distribution: bool,
+    /// List the newest measurement first instead of the oldest.
+    pub newest_first: bool,
+    /// When merging users, readings this close are kept once.
+    pub merge_tolerance: MergeTolerance,
+    /// Unrecognised DATA tags (e.g. a model's extra impedances) shown as numeric
+    /// table columns, read with `Measurement::extra_f32`. Applies on the next start.
+    pub extra_columns: Vec<String>,
+    /// Rows the table shows at first, and adds with each "Load more". At least 1.
+    pub table_page_size: usize,
+    /// Where the scale's SD card mounts. When set, the app watches it and offers
+    /// to load a card as soon as it is inserted.
+    pub card_mount_path: Option<PathBuf>,
+    /// Metric shown large at the top of a user's tab, by user index. Users not
+    /// listed get `Metric::default()`.
+    pub primary_metrics: BTreeMap<usize, Metric>,
+    /// Newest measurement covered by the last "new since last sync" export.
+    pub last_sync: Option<DateTime>,
+    /// Units of the mass and length columns in the CSV and household exports.
+    pub export_units: Units,
+    /// Units of weight, bone mass and height in the table.
+    pub display_units: Units,
+}
+
+pub const FONT_SCALE_RANGE: std::ops::RangeInclusive<f32> = 1.0..=1.5;
+pub const MERGE_SECONDS_RANGE: std::ops::RangeInclusive<i32> = 0..=600;
+pub const MERGE_WEIGHT_KG_RANGE: std::ops::RangeInclusive<f32> = 0.0..=1.0;
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            user_sort: UserSort::default(),
+            number_format: NumberFormat::default(),
+            show_profile_columns: false,
+            compact_table: false,
+            keep_raw_rows: false,
+            show_recomputed: false,
+            muscle_only: false,
+            full_feature_era_only: false,
+            high_contrast: false,
+            font_scale: 1.0,
+            show_fat_distribution: false,
+            newest_first: false,
+            merge_tolerance: MergeTolerance::default(),
+            extra_columns: Vec::new(),
+            table_page_size: 200,
+            card_mount_path: None,
+            primary_metrics: BTreeMap::new(),
+            last_sync: None,
+            export_units: Units::default(),
+            display_units: Units::default(),
+        }
+    }
+}
+
+impl Settings {
+    pub fn view_filter(&self) -> ViewFilter {
+        ViewFilter {
+            muscle_only: self.muscle_only,
+            full_feature_era_only: self.full_feature_era_only,
+        }
+    }
+
+    fn path() -> Option<PathBuf> {
+        Some(
+            dirs::config_dir()?
+                .join(CONFIG_DIR_NAME)
+                .join(CONFIG_FILE_NAME),
+        )
+    }
+
+    /// Missing settings fall back to defaults; an out of range font scale or
+    /// page size is clamped. A malformed file is an error, for the caller to
+    /// report before carrying on with `Settings::default()`.
+    pub fn load() -> Result<Settings, String> {
+        let Some(content) = Self::path().and_then(|p| fs::read_to_string(p).ok()) else {
+            return Ok(Settings::default());
+        };
+        let mut settings: Settings = serde_json::from_str(&content)
+            .map_err(|err| format!("Ignoring malformed {}: {}", CONFIG_FILE_NAME, err))?;
+        settings.font_scale = settings
+            .font_scale
+            .clamp(*FONT_SCALE_RANGE.start(), *FONT_SCALE_RANGE.end());
+        settings.table_page_size = settings.table_page_size.max(1);
+        Ok(settings)
+    }
+
+    pub fn save(&self) -> Result<(), String> {
+        let path = Self::path().ok_or("no config directory on this system")?;
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir).map_err(|err| err.to_string())?;
+        }
+        let json = serde_json::to_string_pretty(self).map_err(|err| err.to_string())?;
+        fs::write(&path, json).map_err(|err| format!("{}: {}", path.display(), err))
+    }
+}
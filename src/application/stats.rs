@@ -0,0 +1,335 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt,
+};
+
+#[cfg(feature = "gui")]
+use super::ViewFilter;
+use super::general_data_structs::{Date, DateTime};
+use super::metric::Metric;
+use super::{Measurement, UserMeasurements};
+
+/// Length of the trailing window the consistency score looks at.
+const CONSISTENCY_WINDOW_WEEKS: i64 = 52;
+
+/// How regularly a user stepped on the scale.
+#[derive(Debug, Default)]
+pub struct Consistency {
+    /// Number of measurements per calendar year.
+    pub per_year: BTreeMap<u16, usize>,
+    /// Weeks in the window with at least one measurement.
+    pub weeks_measured: usize,
+    /// Weeks in the window: the last 52 up to the latest measurement, or fewer
+    /// for shorter histories, so a few stray early readings don't drag the score down.
+    pub weeks_total: usize,
+}
+
+impl Consistency {
+    /// Fraction of weeks with at least one measurement, `None` without measurements.
+    pub fn score(&self) -> Option<f32> {
+        (self.weeks_total > 0).then(|| self.weeks_measured as f32 / self.weeks_total as f32)
+    }
+}
+
+/// Change of one metric between the first and last measurement that carry it.
+#[derive(Debug, Clone, Copy)]
+pub struct ChangeStat {
+    pub metric: Metric,
+    /// Last value minus first value.
+    pub total: f32,
+    pub span_days: f32,
+    pub per_week: f32,
+}
+
+impl ChangeStat {
+    /// `None` when `last` is not after `first`.
+    pub fn between(
+        metric: Metric,
+        (first, first_value): (&DateTime, f32),
+        (last, last_value): (&DateTime, f32),
+    ) -> Option<ChangeStat> {
+        let seconds = last.seconds_since_epoch() - first.seconds_since_epoch();
+        if seconds <= 0 {
+            return None;
+        }
+        let span_days = seconds as f32 / 86_400.0;
+        let total = last_value - first_value;
+
+        Some(ChangeStat {
+            metric,
+            total,
+            span_days,
+            per_week: total / span_days * 7.0,
+        })
+    }
+}
+
+/// Week number as counted by `week_number`.
+pub type Week = i64;
+
+/// Monday-based week counter (1970-01-01 was a Thursday).
+fn week_number(date: &Date) -> Week {
+    (date.days_since_epoch() + 3).div_euclid(7)
+}
+
+/// Consecutive `dates`, sorted, more than `min_gap_days` calendar days apart.
+fn gaps_between(dates: &[&DateTime], min_gap_days: u32) -> Vec<(DateTime, DateTime, u32)> {
+    dates
+        .windows(2)
+        .filter_map(|pair| {
+            let days = pair[1].date().days_since_epoch() - pair[0].date().days_since_epoch();
+            (days > i64::from(min_gap_days))
+                .then(|| (pair[0].clone(), pair[1].clone(), days as u32))
+        })
+        .collect()
+}
+
+/// Calendar unit a streak is counted in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Period {
+    Day,
+    /// Monday to Sunday.
+    Week,
+}
+
+impl Period {
+    fn number(&self, date: &Date) -> i64 {
+        match self {
+            Period::Day => date.days_since_epoch(),
+            Period::Week => week_number(date),
+        }
+    }
+}
+
+impl fmt::Display for Period {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Period::Day => write!(f, "day"),
+            Period::Week => write!(f, "week"),
+        }
+    }
+}
+
+impl UserMeasurements {
+    pub fn consistency(&self) -> Consistency {
+        let mut per_year = BTreeMap::new();
+        let mut weeks = BTreeSet::new();
+        for m in self.measurements.iter().filter(|m| !m.excluded) {
+            let Some(date_time) = &m.date_time else {
+                continue;
+            };
+            let date = date_time.date();
+            *per_year.entry(date.year()).or_insert(0) += 1;
+            weeks.insert(week_number(date));
+        }
+
+        let (Some(&first), Some(&last)) = (weeks.first(), weeks.last()) else {
+            return Consistency::default();
+        };
+        let window_start = first.max(last - CONSISTENCY_WINDOW_WEEKS + 1);
+
+        Consistency {
+            per_year,
+            weeks_measured: weeks.range(window_start..).count(),
+            weeks_total: (last - window_start + 1) as usize,
+        }
+    }
+}
+
+impl UserMeasurements {
+    /// Standard deviation of weight within each week that has two or more
+    /// dated, not excluded readings, oldest week first.
+    pub fn weekly_variance(&self) -> Vec<(Week, f32)> {
+        let mut by_week: BTreeMap<Week, Vec<f32>> = BTreeMap::new();
+        for m in self.measurements.iter().filter(|m| !m.excluded) {
+            if let Some(date_time) = &m.date_time {
+                by_week
+                    .entry(week_number(date_time.date()))
+                    .or_default()
+                    .push(m.weight_kg);
+            }
+        }
+        by_week
+            .into_iter()
+            .filter(|(_, weights)| weights.len() >= 2)
+            .map(|(week, weights)| {
+                let mean = weights.iter().sum::<f32>() / weights.len() as f32;
+                let variance =
+                    weights.iter().map(|w| (w - mean).powi(2)).sum::<f32>() / weights.len() as f32;
+                (week, variance.sqrt())
+            })
+            .collect()
+    }
+
+    /// Median of `weekly_variance`: how much weight typically moves within a
+    /// week. `None` without a week of two or more readings.
+    pub fn typical_fluctuation(&self) -> Option<f32> {
+        let mut spreads: Vec<f32> = self.weekly_variance().into_iter().map(|(_, s)| s).collect();
+        spreads.sort_by(f32::total_cmp);
+        spreads.get(spreads.len() / 2).copied()
+    }
+
+    /// Breaks in weighing: consecutive dated, not excluded measurements more
+    /// than `min_gap_days` calendar days apart, as (last before, first after,
+    /// days between), oldest first.
+    pub fn gaps(&self, min_gap_days: u32) -> Vec<(DateTime, DateTime, u32)> {
+        let mut dates: Vec<&DateTime> = self
+            .measurements
+            .iter()
+            .filter(|m| !m.excluded)
+            .filter_map(|m| m.date_time.as_ref())
+            .collect();
+        dates.sort();
+        gaps_between(&dates, min_gap_days)
+    }
+
+    /// `gaps` among the measurements that pass `filter`, as charted.
+    #[cfg(feature = "gui")]
+    pub(super) fn gaps_in_view(
+        &self,
+        filter: ViewFilter,
+        min_gap_days: u32,
+    ) -> Vec<(DateTime, DateTime, u32)> {
+        let dates: Vec<&DateTime> = self
+            .charted(filter)
+            .into_iter()
+            .filter_map(|m| m.date_time.as_ref())
+            .collect();
+        gaps_between(&dates, min_gap_days)
+    }
+
+    /// Periods holding at least one dated, not excluded measurement.
+    fn measured_periods(&self, period: Period) -> BTreeSet<i64> {
+        self.measurements
+            .iter()
+            .filter(|m| !m.excluded)
+            .filter_map(|m| Some(period.number(m.date_time.as_ref()?.date())))
+            .collect()
+    }
+
+    /// Consecutive periods with a reading, counted back from the one of the
+    /// latest measurement. 0 without dated measurements.
+    pub fn current_streak(&self, period: Period) -> u32 {
+        let periods = self.measured_periods(period);
+        let mut streak = 0;
+        let mut expected = periods.last().copied();
+        for &number in periods.iter().rev() {
+            if Some(number) != expected {
+                break;
+            }
+            streak += 1;
+            expected = Some(number - 1);
+        }
+        streak
+    }
+
+    /// Longest run of consecutive periods with a reading.
+    pub fn longest_streak(&self, period: Period) -> u32 {
+        let mut longest = 0;
+        let mut run = 0;
+        let mut previous: Option<i64> = None;
+        for number in self.measured_periods(period) {
+            run = if previous == Some(number - 1) {
+                run + 1
+            } else {
+                1
+            };
+            longest = longest.max(run);
+            previous = Some(number);
+        }
+        longest
+    }
+}
+
+impl UserMeasurements {
+    /// `None` with fewer than two measurements of `metric` or when they share a timestamp.
+    pub fn overall_change(&self, metric: Metric) -> Option<ChangeStat> {
+        let with_metric: Vec<(&DateTime, f32)> = self
+            .measurements
+            .iter()
+            .filter(|m| !m.excluded)
+            .filter_map(|m| Some((m.date_time.as_ref()?, metric.value(m)?)))
+            .collect();
+        let first = with_metric.iter().min_by(|a, b| a.0.cmp(b.0))?;
+        let last = with_metric.iter().max_by(|a, b| a.0.cmp(b.0))?;
+        ChangeStat::between(metric, *first, *last)
+    }
+}
+
+impl UserMeasurements {
+    /// The dated, not excluded measurement closest in time to `at`; the earlier one wins a tie.
+    pub fn nearest_measurement(&self, at: &DateTime) -> Option<&Measurement> {
+        let target = at.seconds_since_epoch();
+        self.measurements
+            .iter()
+            .filter(|m| !m.excluded)
+            .filter_map(|m| Some((m, m.date_time.as_ref()?)))
+            .min_by_key(|(_, date_time)| {
+                let seconds = date_time.seconds_since_epoch();
+                ((seconds - target).abs(), seconds)
+            })
+            .map(|(m, _)| m)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::application::tests::{user, user_at};
+
+    #[test]
+    fn a_gap_must_exceed_the_minimum() {
+        let u = user(
+            0,
+            &[
+                ("01/01/2020", 80.0),
+                ("08/01/2020", 80.0),
+                ("16/01/2020", 80.0),
+            ],
+        );
+        let gaps = u.gaps(7);
+        assert_eq!(gaps.iter().map(|g| g.2).collect::<Vec<_>>(), [8]);
+        assert_eq!(u.gaps(6).len(), 2);
+    }
+
+    #[test]
+    fn sunday_to_monday_starts_a_new_week() {
+        // 05/01/2020 was a Sunday.
+        let u = user(0, &[("05/01/2020", 80.0), ("06/01/2020", 80.0)]);
+        assert_eq!(u.current_streak(Period::Week), 2);
+        assert_eq!(u.longest_streak(Period::Week), 2);
+        assert_eq!(u.current_streak(Period::Day), 2);
+
+        let same_week = user(0, &[("06/01/2020", 80.0), ("12/01/2020", 80.0)]);
+        assert_eq!(same_week.current_streak(Period::Week), 1);
+        assert_eq!(same_week.longest_streak(Period::Week), 1);
+    }
+
+    #[test]
+    fn an_empty_week_breaks_the_streak() {
+        let u = user(
+            0,
+            &[
+                ("06/01/2020", 80.0),
+                ("13/01/2020", 80.0),
+                ("20/01/2020", 80.0),
+                ("03/02/2020", 80.0),
+            ],
+        );
+        assert_eq!(u.current_streak(Period::Week), 1);
+        assert_eq!(u.longest_streak(Period::Week), 3);
+    }
+
+    #[test]
+    fn no_dated_measurements_is_no_streak() {
+        let undated = user_at(0, &[("00/00/0000", "00:00:00", 80.0)]);
+        assert_eq!(undated.measurements.len(), 1);
+        assert!(undated.measurements[0].date_time.is_none());
+        for u in [user(0, &[]), undated] {
+            assert_eq!(u.current_streak(Period::Week), 0);
+            assert_eq!(u.longest_streak(Period::Week), 0);
+            assert_eq!(u.current_streak(Period::Day), 0);
+            assert_eq!(u.longest_streak(Period::Day), 0);
+        }
+    }
+}
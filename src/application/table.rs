@@ -0,0 +1,569 @@
+use iced::{
+    Element, Length, Theme,
+    widget::{
+        Column, Row, Space, Text, button, container, horizontal_rule, progress_bar, scrollable,
+        stack, text,
+    },
+};
+
+use super::derived::{DerivedColumn, FatDistribution};
+use super::field_tag::FieldTag;
+use super::general_data_structs::{Date, DateTime, Percent};
+use super::metric::Metric;
+use super::number_format::NumberFormat;
+use super::stats::{ChangeStat, Consistency, Period};
+use super::tags::Tag;
+use super::units::Units;
+use super::{Measurement, Message, Profile};
+
+const CELL_WIDTH: f32 = 50.0;
+/// Width of the milestone star button at the start of each row.
+const STAR_WIDTH: f32 = 30.0;
+const FAT_BAR_WIDTH: f32 = 80.0;
+const FAT_BAR_HEIGHT: f32 = 10.0;
+/// iced's default text size, scaled by `font_scale`.
+const BASE_TEXT_SIZE: f32 = 16.0;
+const COMPACT_TEXT_SIZE: f32 = 12.0;
+const PRIMARY_TEXT_SIZE: f32 = 36.0;
+/// Rough average glyph width at `COMPACT_TEXT_SIZE`, used to size compact columns.
+const COMPACT_CHAR_WIDTH: f32 = 7.0;
+const COMPACT_CELL_PADDING: f32 = 6.0;
+
+pub struct TableBuilder<'d> {
+    pub number_format: NumberFormat,
+    /// Show gender and height as echoed by the scale on every row.
+    pub show_profile_columns: bool,
+    /// Size columns to their content with smaller text, for screenshots and printing.
+    pub compact: bool,
+    /// Birth date of the user whose rows are shown, for the computed age.
+    pub birth_date: Date,
+    /// Show age and BMI recomputed from the birth date, weight and height
+    /// instead of the device's values; cells that differ are marked with `*`.
+    pub show_recomputed: bool,
+    /// Multiplies text sizes and column widths, 1.0 to 1.5.
+    pub font_scale: f32,
+    /// Add a stacked bar of the segmental fat distribution to each row.
+    pub show_fat_distribution: bool,
+    /// Registered extra columns, shown after the device ones.
+    pub derived: &'d [DerivedColumn],
+    /// Units of weight, bone mass and height.
+    pub units: Units,
+    /// Off when no measurement of the user has muscle data, so the table
+    /// doesn't show six columns of `-`.
+    pub show_muscle_columns: bool,
+}
+
+impl TableBuilder<'_> {
+    fn text_w100<'a, T>(&self, t: T) -> Text<'a>
+    where
+        T: text::IntoFragment<'a>,
+    {
+        text(t)
+            .width(Length::Fixed(100.0 * self.font_scale))
+            .size(BASE_TEXT_SIZE * self.font_scale)
+    }
+
+    fn text_w50<'a, T>(&self, t: T) -> Text<'a>
+    where
+        T: text::IntoFragment<'a>,
+    {
+        text(t)
+            .width(Length::Fixed(50.0 * self.font_scale))
+            .size(BASE_TEXT_SIZE * self.font_scale)
+    }
+
+    fn number<T>(&self, val: T) -> String
+    where
+        T: ToString,
+    {
+        self.number_format.format(val)
+    }
+
+    /// `number` with a `+` in front of positive values.
+    fn signed(&self, val: f32) -> String {
+        if val > 0.0 {
+            format!("+{}", self.number(val))
+        } else {
+            self.number(val)
+        }
+    }
+
+    fn option_into_string<T>(&self, val: Option<T>) -> String
+    where
+        T: ToString,
+    {
+        match val {
+            Some(v) => self.number(v),
+            None => "-".to_string(),
+        }
+    }
+
+    /// Out of range reads get a trailing `?`.
+    fn percent(&self, val: Percent) -> String {
+        let formatted = self.number(val.value());
+        if val.is_suspicious() {
+            formatted + "?"
+        } else {
+            formatted
+        }
+    }
+
+    fn option_percent(&self, val: Option<Percent>) -> String {
+        match val {
+            Some(v) => self.percent(v),
+            None => "-".to_string(),
+        }
+    }
+
+    fn height(&self, height_cm: f32) -> String {
+        match self.units {
+            Units::Metric => self.number(height_cm),
+            Units::Imperial => Units::feet_inches(height_cm),
+        }
+    }
+
+    pub fn heading<'a>(&self, profile: &'a Profile) -> Column<'a, Message> {
+        let title = iced::widget::row![
+            self.text_w100(FieldTag::Model.header()),
+            self.text_w100(FieldTag::BirthDate.header()),
+            self.text_w100(FieldTag::Gender.header()),
+            self.text_w100(self.units.header(FieldTag::Height)),
+            self.text_w100(FieldTag::ActivityLevel.header()),
+            self.text_w100(FieldTag::BodyType.header()),
+        ]
+        .spacing(10);
+        let content = iced::widget::row![
+            self.text_w100(profile.model.to_string()),
+            self.text_w100(profile.birth_date_dmy.to_string()),
+            self.text_w100(profile.gender.to_string()),
+            self.text_w100(self.height(profile.height_cm)),
+            self.text_w100(profile.activity_level_code.to_string()),
+            self.text_w100(profile.body_type_code.to_string()),
+        ]
+        .spacing(10);
+
+        iced::widget::column![title, content]
+    }
+
+    /// e.g. "Weight: -6.2 kg over 90 days (-0.48 kg/week)", in `units`.
+    pub fn change<'a>(&self, change: &ChangeStat) -> Text<'a> {
+        let unit = self.units.metric_unit(change.metric);
+        let total = self.units.convert_metric(change.metric, change.total);
+        let per_week = self.units.convert_metric(change.metric, change.per_week);
+        text(format!(
+            "{}: {} {} over {} days ({} {}/week)",
+            change.metric,
+            self.signed((total * 10.0).round() / 10.0),
+            unit,
+            self.number(change.span_days.round()),
+            self.signed((per_week * 100.0).round() / 100.0),
+            unit
+        ))
+    }
+
+    /// Latest value of `metric` in large type, with an arrow for the change
+    /// since the reading before it.
+    pub fn primary_metric<'a>(
+        &self,
+        metric: Metric,
+        series: &[(DateTime, f32)],
+    ) -> Row<'a, Message> {
+        let Some((_, latest)) = series.last() else {
+            return iced::widget::row![text(format!("No {} data", metric))];
+        };
+        let arrow = match series.len().checked_sub(2).map(|i| latest - series[i].1) {
+            Some(change) if change > 0.0 => "↑",
+            Some(change) if change < 0.0 => "↓",
+            _ => "→",
+        };
+        let latest = self.units.convert_metric(metric, *latest);
+        iced::widget::row![
+            text(format!(
+                "{} {}",
+                self.number((latest * 10.0).round() / 10.0),
+                self.units.metric_unit(metric)
+            ))
+            .size(PRIMARY_TEXT_SIZE * self.font_scale),
+            text(arrow).size(PRIMARY_TEXT_SIZE * self.font_scale),
+        ]
+        .spacing(10)
+        .align_y(iced::Alignment::Center)
+    }
+
+    /// e.g. "Within a week your weight typically varies ±1.2 kg", from
+    /// `UserMeasurements::typical_fluctuation`, in `units`.
+    pub fn fluctuation<'a>(&self, std_dev_kg: f32) -> Text<'a> {
+        let std_dev = self.units.convert_metric(Metric::WeightKg, std_dev_kg);
+        text(format!(
+            "Within a week your weight typically varies ±{} {}, which is normal water and food",
+            self.number((std_dev * 10.0).round() / 10.0),
+            self.units.metric_unit(Metric::WeightKg)
+        ))
+    }
+
+    /// The scale's daily calorie intake next to `derived::tdee_kcal`, with what each means.
+    pub fn calorie_comparison<'a>(&self, dci_kcal: u16, tdee_kcal: f32) -> Column<'a, Message> {
+        let difference = tdee_kcal.round() as i32 - i32::from(dci_kcal);
+        iced::widget::column![
+            text(format!(
+                "Scale daily calorie intake: {} kcal",
+                self.number(dci_kcal)
+            )),
+            text(format!(
+                "Estimated TDEE: {} kcal ({} kcal)",
+                self.number(tdee_kcal.round()),
+                self.signed(difference as f32)
+            )),
+            text(
+                "The scale's figure is its own maintenance estimate. TDEE here is \
+                 370 + 21.6 × lean mass (Katch-McArdle BMR) times an activity factor \
+                 of 1.375, 1.55 or 1.725 for activity level 1, 2 or 3."
+            )
+            .size(12),
+        ]
+        .spacing(2)
+    }
+
+    /// e.g. "Longest break: 34 days (from 02/03/2024)".
+    pub fn longest_break<'a>(&self, from: &DateTime, days: u32) -> Text<'a> {
+        text(format!(
+            "Longest break: {} days (from {})",
+            self.number(days),
+            from.date()
+        ))
+    }
+
+    /// e.g. "7-day streak (longest 21)".
+    pub fn streak<'a>(&self, period: Period, current: u32, longest: u32) -> Text<'a> {
+        text(format!(
+            "{}-{} streak (longest {})",
+            self.number(current),
+            period,
+            self.number(longest)
+        ))
+    }
+
+    pub fn consistency<'a>(&self, consistency: &Consistency) -> Column<'a, Message> {
+        let mut col = iced::widget::column![].spacing(2);
+        if let Some(score) = consistency.score() {
+            col = col.push(text(format!(
+                "Weighed in {} of {} weeks ({}%)",
+                consistency.weeks_measured,
+                consistency.weeks_total,
+                self.number((score * 100.0).round())
+            )));
+        }
+
+        let max = consistency.per_year.values().copied().max().unwrap_or(0) as f32;
+        for (year, count) in &consistency.per_year {
+            col = col.push(
+                iced::widget::row![
+                    self.text_w50(year.to_string()),
+                    progress_bar(0.0..=max, *count as f32)
+                        .width(Length::Fixed(200.0))
+                        .height(Length::Fixed(10.0)),
+                    self.text_w50(self.number(count)),
+                ]
+                .spacing(10)
+                .align_y(iced::Alignment::Center),
+            );
+        }
+        col
+    }
+
+    /// Device fields in table order; gender and height only with
+    /// `show_profile_columns`, muscle only with `show_muscle_columns`.
+    fn tag_columns(&self) -> Vec<FieldTag> {
+        let mut tags = vec![FieldTag::Age];
+        if self.show_profile_columns {
+            tags.extend([FieldTag::Gender, FieldTag::Height]);
+        }
+        tags.extend([
+            FieldTag::ActivityLevel,
+            FieldTag::BodyType,
+            FieldTag::Weight,
+            FieldTag::Bmi,
+            FieldTag::FatPercent,
+            FieldTag::FatTrunk,
+            FieldTag::FatRightArm,
+            FieldTag::FatLeftArm,
+            FieldTag::FatRightLeg,
+            FieldTag::FatLeftLeg,
+        ]);
+        if self.show_muscle_columns {
+            tags.extend([
+                FieldTag::MusclePercent,
+                FieldTag::MuscleTrunk,
+                FieldTag::MuscleRightArm,
+                FieldTag::MuscleLeftArm,
+                FieldTag::MuscleRightLeg,
+                FieldTag::MuscleLeftLeg,
+            ]);
+        }
+        tags.extend([
+            FieldTag::BoneMass,
+            FieldTag::WaterPercent,
+            FieldTag::VisceralFat,
+            FieldTag::MetabolicAge,
+            FieldTag::DailyCalorieIntake,
+        ]);
+        tags
+    }
+
+    fn headers(&self) -> Vec<String> {
+        let mut headers = vec!["Date and time".to_string()];
+        headers.extend(self.tag_columns().iter().map(|tag| match tag {
+            FieldTag::Age | FieldTag::Bmi if self.show_recomputed => {
+                format!("{} (recomputed)", tag.header())
+            }
+            _ => self.units.header(*tag),
+        }));
+        headers.extend(["Phase angle (°)".to_string(), "Impedance (Ω)".to_string()]);
+        headers.extend(self.derived.iter().map(|column| column.name.clone()));
+        headers
+    }
+
+    fn tag_cell(&self, tag: FieldTag, measurement: &Measurement) -> String {
+        match tag {
+            FieldTag::Gender => measurement.gender.to_string(),
+            FieldTag::Age => {
+                match measurement.effective_age(&self.birth_date, self.show_recomputed) {
+                    Some(age) if age != u16::from(measurement.age_years) => self.number(age) + "*",
+                    age => self.option_into_string(age),
+                }
+            }
+            FieldTag::Height => self.height(measurement.height_cm),
+            FieldTag::ActivityLevel => self.number(measurement.activity_level_code),
+            FieldTag::BodyType => self.number(measurement.body_type_code),
+            FieldTag::Weight => self.number(self.units.convert(tag, measurement.weight_kg)),
+            FieldTag::Bmi => match measurement.computed_bmi() {
+                Some(bmi) if self.show_recomputed => {
+                    let rounded = (bmi * 10.0).round() / 10.0;
+                    if (rounded - measurement.bmi).abs() > 0.05 {
+                        self.number(rounded) + "*"
+                    } else {
+                        self.number(rounded)
+                    }
+                }
+                _ => self.number(measurement.bmi),
+            },
+            FieldTag::FatPercent => self.percent(measurement.fat_percent),
+            FieldTag::FatRightArm => self.option_percent(measurement.fat_right_arm_pct),
+            FieldTag::FatLeftArm => self.option_percent(measurement.fat_left_arm_pct),
+            FieldTag::FatRightLeg => self.option_percent(measurement.fat_right_leg_pct),
+            FieldTag::FatLeftLeg => self.option_percent(measurement.fat_left_leg_pct),
+            FieldTag::FatTrunk => self.option_percent(measurement.fat_trunk_pct),
+            FieldTag::MusclePercent => self.option_percent(measurement.muscle_percent),
+            FieldTag::MuscleRightArm => self.option_percent(measurement.muscle_right_arm_pct),
+            FieldTag::MuscleLeftArm => self.option_percent(measurement.muscle_left_arm_pct),
+            FieldTag::MuscleRightLeg => self.option_percent(measurement.muscle_right_leg_pct),
+            FieldTag::MuscleLeftLeg => self.option_percent(measurement.muscle_left_leg_pct),
+            FieldTag::MuscleTrunk => self.option_percent(measurement.muscle_trunk_pct),
+            FieldTag::BoneMass => {
+                self.option_into_string(measurement.bone_kg.map(|kg| self.units.convert(tag, kg)))
+            }
+            FieldTag::WaterPercent => self.option_percent(measurement.water_percent),
+            FieldTag::VisceralFat => self.option_into_string(measurement.visceral_fat_rating),
+            FieldTag::MetabolicAge => self.option_into_string(measurement.metabolic_age_years),
+            FieldTag::DailyCalorieIntake => {
+                self.option_into_string(measurement.daily_calorie_intake_kcal)
+            }
+            // not kept on `Measurement`
+            FieldTag::Model
+            | FieldTag::BirthDate
+            | FieldTag::Date
+            | FieldTag::Time
+            | FieldTag::Checksum => "-".to_string(),
+        }
+    }
+
+    /// Formatted cells of one row, in the same order as `headers`.
+    fn cells(&self, measurement: &Measurement) -> Vec<String> {
+        let mut cells = vec![match &measurement.date_time {
+            Some(date_time) => date_time.to_string(),
+            None => "clock unset".to_string(),
+        }];
+        cells.extend(
+            self.tag_columns()
+                .into_iter()
+                .map(|tag| self.tag_cell(tag, measurement)),
+        );
+        cells.extend([
+            self.option_into_string(measurement.phase_angle),
+            self.option_into_string(measurement.impedance_ohms),
+        ]);
+        cells.extend(self.derived.iter().map(|column| {
+            self.option_into_string(
+                (column.compute)(measurement).map(|v| (v * 10.0).round() / 10.0),
+            )
+        }));
+        cells
+    }
+
+    /// Fixed widths normally; in compact mode each column fits its longest
+    /// cell or header word, so headers wrap instead of being cut off.
+    fn column_widths(&self, headers: &[String], rows: &[Vec<String>]) -> Vec<f32> {
+        if !self.compact {
+            return vec![CELL_WIDTH * self.font_scale; headers.len()];
+        }
+        headers
+            .iter()
+            .enumerate()
+            .map(|(i, header)| {
+                let longest_word = header
+                    .split_whitespace()
+                    .map(|w| w.chars().count())
+                    .max()
+                    .unwrap_or(0);
+                let longest_cell = rows
+                    .iter()
+                    .map(|row| row[i].chars().count())
+                    .max()
+                    .unwrap_or(0);
+                longest_word.max(longest_cell) as f32 * COMPACT_CHAR_WIDTH * self.font_scale
+                    + COMPACT_CELL_PADDING
+            })
+            .collect()
+    }
+
+    fn cell<'a>(&self, content: &str, width: f32) -> Text<'a> {
+        let size = if self.compact {
+            COMPACT_TEXT_SIZE
+        } else {
+            BASE_TEXT_SIZE
+        };
+        text(content.to_string())
+            .width(Length::Fixed(width))
+            .size(size * self.font_scale)
+    }
+
+    /// Stacked bar of `FatDistribution::segments`, one colour per segment.
+    fn fat_distribution_bar<'a>(&self, distribution: &FatDistribution) -> Row<'a, Message> {
+        let mut bar = iced::widget::row![].width(Length::Fixed(FAT_BAR_WIDTH * self.font_scale));
+        for (i, (_, share)) in distribution.segments().into_iter().enumerate() {
+            let portion = (share * 1000.0).round() as u16;
+            if portion == 0 {
+                continue;
+            }
+            bar = bar.push(
+                container(text(""))
+                    .width(Length::FillPortion(portion))
+                    .height(Length::Fixed(FAT_BAR_HEIGHT))
+                    .style(move |theme: &Theme| {
+                        let palette = theme.extended_palette();
+                        let color = [
+                            palette.primary.strong.color,
+                            palette.success.base.color,
+                            palette.success.weak.color,
+                            palette.danger.base.color,
+                            palette.danger.weak.color,
+                        ][i];
+                        container::Style::default().background(color)
+                    }),
+            );
+        }
+        bar
+    }
+
+    fn chip<'a>(tag: Tag) -> container::Container<'a, Message> {
+        container(text(tag.to_string()).size(11))
+            .padding([1, 6])
+            .style(container::rounded_box)
+    }
+
+    /// Lists `measurements[i]` for each `i` in `visible`, in that order.
+    pub fn body<'a>(
+        &self,
+        user: usize,
+        measurements: &'a [Measurement],
+        visible: &[usize],
+        tags: &[Vec<Tag>],
+    ) -> Column<'a, Message> {
+        let visible: Vec<(&'a Measurement, &Vec<Tag>)> = visible
+            .iter()
+            .map(|&i| (&measurements[i], &tags[i]))
+            .collect();
+        let headers = self.headers();
+        let rows: Vec<Vec<String>> = visible.iter().map(|(m, _)| self.cells(m)).collect();
+        let widths = self.column_widths(&headers, &rows);
+
+        // Blank over the star and edit buttons, so each header sits over its column.
+        let mut title = iced::widget::row![
+            Space::with_width(Length::Fixed(STAR_WIDTH)),
+            Space::with_width(Length::Fixed(CELL_WIDTH)),
+        ]
+        .spacing(1);
+        for (header, width) in headers.iter().zip(&widths) {
+            title = title.push(self.cell(header, *width));
+        }
+        if self.show_fat_distribution {
+            title =
+                title.push(self.cell("Fat trunk / arms / legs", FAT_BAR_WIDTH * self.font_scale));
+        }
+
+        let mut col = iced::widget::column![];
+
+        for ((measurement, tags), cells) in visible.into_iter().zip(&rows) {
+            let edit_label = if measurement.excluded {
+                "excl."
+            } else {
+                "edit"
+            };
+            let star = if measurement.milestone { "★" } else { "☆" };
+            let mut r = iced::widget::row![
+                button(text(star).size(12))
+                    .width(Length::Fixed(STAR_WIDTH))
+                    .on_press(Message::ToggleMilestone(user, measurement.fingerprint())),
+                button(text(edit_label).size(12))
+                    .width(Length::Fixed(CELL_WIDTH))
+                    .on_press(Message::EditMeasurement(user, measurement.fingerprint())),
+            ]
+            .spacing(1);
+            let mut values = iced::widget::row![].spacing(1);
+            for (cell, width) in cells.iter().zip(&widths) {
+                values = values.push(self.cell(cell, *width));
+            }
+            // Excluded rows are struck through: a rule drawn across the middle of the values.
+            r = if measurement.excluded {
+                r.push(stack![
+                    values,
+                    container(horizontal_rule(1))
+                        .width(Length::Fill)
+                        .center_y(Length::Fill),
+                ])
+            } else {
+                r.push(values)
+            };
+            if self.show_fat_distribution {
+                r = r.push(match measurement.fat_distribution() {
+                    Some(distribution) => Element::from(self.fat_distribution_bar(&distribution)),
+                    None => Element::from(self.cell("-", FAT_BAR_WIDTH * self.font_scale)),
+                });
+            }
+            for tag in tags {
+                r = r.push(Self::chip(*tag));
+            }
+            if measurement.excluded {
+                col = col.push(container(r).style(|theme: &Theme| container::Style {
+                    text_color: Some(theme.extended_palette().background.strong.color),
+                    ..container::Style::default()
+                }));
+            } else {
+                col = col.push(r);
+            }
+            col = col.push(horizontal_rule(1));
+        }
+
+        iced::widget::column![
+            title,
+            horizontal_rule(2),
+            scrollable(col).direction(scrollable::Direction::Vertical(
+                scrollable::Scrollbar::new()
+                    .width(14)
+                    .scroller_width(8)
+                    .spacing(4)
+            )) // .width(Length::Fill)
+               // .height(Length::Fill)
+        ]
+    }
+}
@@ -0,0 +1,92 @@
+use std::fmt;
+
+use super::UserMeasurements;
+
+/// Morning readings are the ones taken from 04:00 up to, but not including, 10:00.
+const MORNING_HOURS: std::ops::Range<u8> = 4..10;
+/// A drop of at least this share of body weight within a few hours is treated as sweat loss.
+const WORKOUT_DROP_RATIO: f32 = 0.01;
+const WORKOUT_WINDOW_SECONDS: i64 = 6 * 3_600;
+/// A gain of at least this share within two days is more water and glycogen than tissue.
+const REFEED_GAIN_RATIO: f32 = 0.015;
+const REFEED_WINDOW_SECONDS: i64 = 48 * 3_600;
+
+/// Label inferred for a measurement from its time and neighbours, never stored.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Tag {
+    Morning,
+    PostWorkout,
+    Refeed,
+    /// Echoed gender or height doesn't match the profile.
+    WrongUser,
+    /// The row's checksum failed.
+    BadChecksum,
+}
+
+impl fmt::Display for Tag {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Tag::Morning => write!(f, "morning"),
+            Tag::PostWorkout => write!(f, "post-workout"),
+            Tag::Refeed => write!(f, "refeed"),
+            Tag::WrongUser => write!(f, "wrong user?"),
+            Tag::BadChecksum => write!(f, "corrupt?"),
+        }
+    }
+}
+
+impl UserMeasurements {
+    /// Tags for each entry of `measurements`, in the same order.
+    ///
+    /// Each reading is compared with the previous dated, not excluded one:
+    /// a weight drop of 1% or more within 6 hours marks it post-workout and
+    /// a gain of 1.5% or more within 48 hours marks it a refeed.
+    /// Undated and excluded readings only get the tags that need no neighbour.
+    /// Readings flagged as possibly the wrong user or failing their checksum
+    /// are tagged as such.
+    pub fn auto_tags(&self) -> Vec<Vec<Tag>> {
+        let mut tags = vec![Vec::new(); self.measurements.len()];
+
+        let mut chronological: Vec<usize> = (0..self.measurements.len())
+            .filter(|&i| {
+                let m = &self.measurements[i];
+                m.date_time.is_some() && !m.excluded
+            })
+            .collect();
+        chronological.sort_by_key(|&i| &self.measurements[i]);
+
+        for pair in chronological.windows(2) {
+            let (previous, current) = (&self.measurements[pair[0]], &self.measurements[pair[1]]);
+            let (Some(previous_at), Some(current_at)) = (&previous.date_time, &current.date_time)
+            else {
+                continue;
+            };
+            if previous.weight_kg <= 0.0 {
+                continue;
+            }
+            let elapsed = current_at.seconds_since_epoch() - previous_at.seconds_since_epoch();
+            let ratio = (current.weight_kg - previous.weight_kg) / previous.weight_kg;
+            if ratio <= -WORKOUT_DROP_RATIO && elapsed <= WORKOUT_WINDOW_SECONDS {
+                tags[pair[1]].push(Tag::PostWorkout);
+            } else if ratio >= REFEED_GAIN_RATIO && elapsed <= REFEED_WINDOW_SECONDS {
+                tags[pair[1]].push(Tag::Refeed);
+            }
+        }
+
+        for (m, tags) in self.measurements.iter().zip(&mut tags) {
+            if let Some(date_time) = &m.date_time
+                && MORNING_HOURS.contains(&date_time.time().hours())
+            {
+                tags.insert(0, Tag::Morning);
+            }
+            if m.possibly_wrong_user {
+                tags.push(Tag::WrongUser);
+            }
+            if !m.checksum_ok {
+                tags.push(Tag::BadChecksum);
+            }
+        }
+
+        tags
+    }
+}
@@ -0,0 +1,138 @@
+use std::{borrow::Cow, fmt, str::FromStr};
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use super::field_tag::FieldTag;
+use super::general_data_structs::ParseEnumError;
+use super::metric::Metric;
+
+const LB_PER_KG: f32 = 2.204_622_6;
+const CM_PER_INCH: f32 = 2.54;
+
+/// Units of the mass and length columns in the table and exports. Measurements
+/// are always kept in metric, as the scale writes them; only the output is converted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum Units {
+    #[default]
+    Metric,
+    /// Pounds and inches.
+    Imperial,
+}
+
+impl Units {
+    pub const ALL: [Units; 2] = [Units::Metric, Units::Imperial];
+
+    /// Unit of `tag` in this system; tags other than mass and length keep theirs.
+    pub fn unit(&self, tag: FieldTag) -> Option<&'static str> {
+        match (self, tag) {
+            (Units::Imperial, FieldTag::Weight | FieldTag::BoneMass) => Some("lb"),
+            (Units::Imperial, FieldTag::Height) => Some("in"),
+            _ => tag.unit(),
+        }
+    }
+
+    /// Label of `tag`, with the unit for the mass and length fields so a
+    /// reader can't mistake pounds for kilograms, e.g. "Weight (lb)".
+    pub fn label(&self, tag: FieldTag) -> Cow<'static, str> {
+        match (tag, self.unit(tag)) {
+            (FieldTag::Weight | FieldTag::BoneMass | FieldTag::Height, Some(unit)) => {
+                Cow::Owned(format!("{} ({})", tag.label(), unit))
+            }
+            _ => Cow::Borrowed(tag.label()),
+        }
+    }
+
+    /// Table header of `tag` in this system. Imperial heights are shown as
+    /// feet and inches, see `feet_inches`.
+    pub fn header(&self, tag: FieldTag) -> String {
+        match (self, tag, self.unit(tag)) {
+            (Units::Imperial, FieldTag::Height, _) => format!("{} (ft, in)", tag.label()),
+            (_, _, Some(unit)) => format!("{} ({})", tag.label(), unit),
+            _ => tag.label().to_string(),
+        }
+    }
+
+    /// `height_cm` to the nearest inch, e.g. `5'9"`.
+    pub fn feet_inches(height_cm: f32) -> String {
+        let inches = (height_cm / CM_PER_INCH).round() as u32;
+        format!("{}'{}\"", inches / 12, inches % 12)
+    }
+
+    /// `value` of `tag`, given in metric, in this system. Converted values are
+    /// rounded to 0.1, the scale's own resolution.
+    pub fn convert(&self, tag: FieldTag, value: f32) -> f32 {
+        let factor = match (self, tag) {
+            (Units::Imperial, FieldTag::Weight | FieldTag::BoneMass) => LB_PER_KG,
+            (Units::Imperial, FieldTag::Height) => 1.0 / CM_PER_INCH,
+            _ => return value,
+        };
+        (value * factor * 10.0).round() / 10.0
+    }
+
+    /// Unit of `metric` in this system; only the mass metrics change.
+    pub fn metric_unit(&self, metric: Metric) -> &'static str {
+        match (self, metric) {
+            (Units::Imperial, Metric::WeightKg | Metric::BoneKg) => "lb",
+            _ => metric.unit(),
+        }
+    }
+
+    /// `value` of `metric`, given in metric, in this system. Unlike `convert`
+    /// it isn't rounded, so small changes and weekly rates keep their digits.
+    pub fn convert_metric(&self, metric: Metric, value: f32) -> f32 {
+        match (self, metric) {
+            (Units::Imperial, Metric::WeightKg | Metric::BoneKg) => value * LB_PER_KG,
+            _ => value,
+        }
+    }
+}
+
+impl fmt::Display for Units {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Units::Metric => write!(f, "kg, cm"),
+            Units::Imperial => write!(f, "lb, in"),
+        }
+    }
+}
+
+/// Accepts "metric"/"imperial" (any case) or the `Display` output.
+impl FromStr for Units {
+    type Err = ParseEnumError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "metric" | "kg, cm" => Ok(Units::Metric),
+            "imperial" | "lb, in" => Ok(Units::Imperial),
+            _ => Err(ParseEnumError::new("units", s)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn units_round_trip_through_display() {
+        for units in Units::ALL {
+            assert_eq!(units.to_string().parse(), Ok(units));
+        }
+        assert_eq!("Imperial".parse(), Ok(Units::Imperial));
+        assert!("stones".parse::<Units>().is_err());
+    }
+
+    #[test]
+    fn only_mass_metrics_are_converted() {
+        let imperial = Units::Imperial;
+        assert_eq!(imperial.metric_unit(Metric::WeightKg), "lb");
+        assert_eq!(imperial.metric_unit(Metric::BoneKg), "lb");
+        assert_eq!(imperial.metric_unit(Metric::FatPercent), "%");
+        assert_eq!(Units::Metric.metric_unit(Metric::WeightKg), "kg");
+        assert!((imperial.convert_metric(Metric::WeightKg, 0.48) - 1.0582).abs() < 1e-3);
+        assert_eq!(imperial.convert_metric(Metric::FatPercent, 20.5), 20.5);
+        assert_eq!(Units::Metric.convert_metric(Metric::WeightKg, 80.0), 80.0);
+    }
+}
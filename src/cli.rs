@@ -0,0 +1,82 @@
+use std::{
+    env,
+    path::{Path, PathBuf},
+    process::ExitCode,
+};
+
+use tanita::{model::Gender, parser::TanitaParser};
+
+/// Usage line naming the program as it was started, or as it was built.
+fn usage() -> String {
+    let program = env::args()
+        .next()
+        .and_then(|arg0| {
+            Path::new(&arg0)
+                .file_name()
+                .map(|name| name.to_string_lossy().into_owned())
+        })
+        .unwrap_or_else(|| env!("CARGO_BIN_NAME").to_string());
+    format!("usage: {} validate|profiles <GRAPHV1 dir>", program)
+}
+
+/// Handles command line subcommands. Returns `None` when no subcommand was
+/// given and the GUI should start instead.
+pub fn run(args: &[String]) -> Option<ExitCode> {
+    let (command, rest) = args.split_first()?;
+    match command.as_str() {
+        "validate" => Some(validate(rest)),
+        "profiles" => Some(profiles(rest)),
+        _ => {
+            eprintln!("unknown command: {}\n{}", command, usage());
+            Some(ExitCode::from(2))
+        }
+    }
+}
+
+/// Exits 0 when the folder passes every structural check, 1 otherwise.
+fn validate(args: &[String]) -> ExitCode {
+    let [dir] = args else {
+        eprintln!("{}", usage());
+        return ExitCode::from(2);
+    };
+
+    let errors = TanitaParser::new(PathBuf::from(dir)).validate();
+    if errors.is_empty() {
+        eprintln!("{}: OK", dir);
+        return ExitCode::SUCCESS;
+    }
+
+    for error in &errors {
+        eprintln!("  {}", error);
+    }
+    eprintln!("{}: {} problem(s) found", dir, errors.len());
+    ExitCode::FAILURE
+}
+
+/// Prints one line per user profile without reading any measurements.
+fn profiles(args: &[String]) -> ExitCode {
+    let [dir] = args else {
+        eprintln!("{}", usage());
+        return ExitCode::from(2);
+    };
+
+    match TanitaParser::new(PathBuf::from(dir)).get_profiles() {
+        Ok(profiles) => {
+            for (index, profile) in profiles {
+                println!(
+                    "{}\t{}\t{}\t{}\t{} cm",
+                    index + 1,
+                    profile.model,
+                    Gender::from(profile.gender_code),
+                    profile.birth_date_dmy,
+                    profile.height_cm
+                );
+            }
+            ExitCode::SUCCESS
+        }
+        Err(error) => {
+            eprintln!("{}: {}", dir, error);
+            ExitCode::FAILURE
+        }
+    }
+}
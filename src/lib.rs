@@ -0,0 +1,44 @@
+//! Reads the CSV files a Tanita BC-601 writes to its SD card, and the iced
+//! viewer built on top. `parser` and `model` don't need any iced types, so
+//! the reading side can be used from other programs without the window.
+//!
+//! The `serde` feature derives `Serialize`/`Deserialize` on the model types.
+//! The default `gui` feature builds `Application` and pulls in iced; it turns
+//! on `serde`, as the viewer stores its settings and edits as JSON. With
+//! `default-features = false` only `parser` and `model` are built, without any
+//! GUI dependency.
+
+mod application;
+
+#[cfg(feature = "gui")]
+pub use application::Application;
+#[cfg(feature = "gui")]
+pub use application::derived::DeriveFn;
+
+/// Finding and reading a GRAPHV1 folder into raw, per-user records.
+pub mod parser {
+    pub use crate::application::parser::{
+        DataRaw, DatasetWarning, ParseProgress, ParseWarning, ParsedDataset, ParserConfig, ProfRaw,
+        RawUserRecord, RowParseError, TanitaPair, TanitaParser, TanitaResult,
+        TanitaValidationError, WarningKind, find_card_folder, find_tanita_folders,
+    };
+}
+
+/// Interpreted users and measurements built from `parser` records with
+/// `UserMeasurements::from_raw`.
+pub mod model {
+    pub use crate::application::export::{
+        export_household_timeline, export_household_timeline_since,
+    };
+    pub use crate::application::general_data_structs::{
+        ActivityLevel, BodyType, Date, DateTime, DeviceModel, Gender, ParseEnumError, Percent, Time,
+    };
+    pub use crate::application::metric::Metric;
+    pub use crate::application::norms::BmiCategory;
+    #[cfg(feature = "serde")]
+    pub use crate::application::schema::user_measurements_schema;
+    pub use crate::application::units::Units;
+    pub use crate::application::{
+        Measurement, Profile, UserMeasurements, from_per_user, into_per_user,
+    };
+}
@@ -1,5 +1,51 @@
-mod application;
+use std::{
+    env,
+    process::{Command, ExitCode},
+};
 
-fn main() -> iced::Result {
-    application::Application::run()
+mod cli;
+
+/// Renderer override understood by iced; `tiny-skia` draws on the CPU.
+const BACKEND_ENV_VAR: &str = "ICED_BACKEND";
+const SOFTWARE_BACKEND: &str = "tiny-skia";
+
+fn main() -> ExitCode {
+    let args: Vec<String> = env::args().skip(1).collect();
+    if let Some(exit_code) = cli::run(&args) {
+        return exit_code;
+    }
+
+    match tanita::Application::run() {
+        Ok(()) => ExitCode::SUCCESS,
+        // The window loop can only be created once per process, so the fallback is a relaunch.
+        Err(iced::Error::GraphicsCreationFailed(err)) if env::var_os(BACKEND_ENV_VAR).is_none() => {
+            eprintln!("Could not start the GPU renderer ({err}), retrying with the software one");
+            relaunch_with_software_renderer()
+        }
+        Err(err) => {
+            eprintln!("Failed to start: {err}");
+            eprintln!(
+                "On VMs and remote desktops try running with {BACKEND_ENV_VAR}={SOFTWARE_BACKEND}"
+            );
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn relaunch_with_software_renderer() -> ExitCode {
+    let status = env::current_exe().and_then(|exe| {
+        Command::new(exe)
+            .args(env::args_os().skip(1))
+            .env(BACKEND_ENV_VAR, SOFTWARE_BACKEND)
+            .status()
+    });
+
+    match status {
+        Ok(status) if status.success() => ExitCode::SUCCESS,
+        Ok(_) => ExitCode::FAILURE,
+        Err(err) => {
+            eprintln!("Failed to relaunch with the software renderer: {err}");
+            ExitCode::FAILURE
+        }
+    }
 }